@@ -0,0 +1,651 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::country::Country;
+use crate::money::{self, Money};
+use crate::taxonomy::Level;
+use crate::zones::ZoneSource;
+use crate::{
+    Carrier, DutiesTerm, Order, PackageType, Province, Region, Scope, ShippingMethod,
+    UnexpectedError, WeightRange,
+};
+
+// AVERAGES
+//
+// Orders are always grouped by region, or by shipping zone with `--area zone`,
+// then by the taxonomy level chosen with `--level` (the shipping method unless
+// told otherwise). Extra dimensions can
+// be added per run with `--group-by`; each one splits every average and adds
+// a column to the output files.
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Dimension {
+    Carrier,
+    Scope,
+    Duties,
+    Package,
+    Country,
+    CountryGroup,
+    State,
+    Zone,
+}
+
+impl Dimension {
+    pub fn from_name(name: &str) -> Result<Dimension, UnexpectedError> {
+        match name.trim() {
+            "carrier" => Ok(Dimension::Carrier),
+            "scope" => Ok(Dimension::Scope),
+            "duties" => Ok(Dimension::Duties),
+            "package" => Ok(Dimension::Package),
+            "country" => Ok(Dimension::Country),
+            "country_group" => Ok(Dimension::CountryGroup),
+            "state" => Ok(Dimension::State),
+            "zone" => Ok(Dimension::Zone),
+            _ => Err(UnexpectedError {
+                message: format!("Unknown grouping \"{}\".", name),
+            }),
+        }
+    }
+
+    /// Parses a comma-separated list such as "carrier,scope".
+    pub fn list_from_str(names: &str) -> Result<Vec<Dimension>, UnexpectedError> {
+        names
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+            .map(Dimension::from_name)
+            .collect()
+    }
+}
+
+/// What the averages are grouped by before anything else.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Area {
+    /// The destination's region in the region scheme.
+    #[default]
+    Region,
+    /// The shipping zone from the origin, as "Zone 1" to "Zone 9". Orders
+    /// without a zone keep their region.
+    Zone,
+}
+
+impl Area {
+    pub fn from_name(name: &str) -> Result<Area, UnexpectedError> {
+        match name.trim() {
+            "region" => Ok(Area::Region),
+            "zone" => Ok(Area::Zone),
+            _ => Err(UnexpectedError {
+                message: format!("Unknown area \"{}\".", name),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+struct GroupKey {
+    region: Region,
+    shipping_method: Option<ShippingMethod>,
+    carrier: Option<Carrier>,
+    product: Option<String>,
+    scope: Option<Scope>,
+    duties: Option<DutiesTerm>,
+    package: Option<PackageType>,
+    country: Option<String>,
+    country_group: Option<String>,
+    /// `Some(None)` for orders without a state when grouping by state.
+    state: Option<Option<Province>>,
+    /// `Some(None)` for orders without a zone when grouping by zone.
+    zone: Option<Option<u8>>,
+    /// Set whenever grouping by zone, so chart and estimated zones aren't
+    /// mixed; `Some(None)` for keys without a zone.
+    zone_source: Option<Option<ZoneSource>>,
+}
+
+impl GroupKey {
+    fn new(order: &Order, region: Region, level: Level, group_by: &[Dimension]) -> GroupKey {
+        let has = |dimension: Dimension| group_by.contains(&dimension);
+        GroupKey {
+            region,
+            shipping_method: level.has_tier().then(|| order.shipping_method.clone()),
+            carrier: (level.has_carrier() || has(Dimension::Carrier))
+                .then(|| order.carrier.clone()),
+            product: level.has_product().then(|| order.product.clone()),
+            scope: has(Dimension::Scope).then(|| order.scope.clone()),
+            duties: has(Dimension::Duties).then(|| order.duties.clone()),
+            package: has(Dimension::Package).then(|| order.package.clone()),
+            country: has(Dimension::Country).then(|| {
+                order
+                    .country
+                    .as_ref()
+                    .map_or_else(|| String::from("Unknown"), Country::name)
+            }),
+            state: has(Dimension::State).then_some(order.state),
+            zone: has(Dimension::Zone).then_some(order.zone),
+            zone_source: None,
+            country_group: has(Dimension::CountryGroup).then(|| {
+                order
+                    .country
+                    .as_ref()
+                    .map_or_else(|| String::from("Unknown"), Country::group)
+            }),
+        }
+    }
+}
+
+struct Counter {
+    total_retail_cost: Money,
+    total_item_count: u32,
+    total_shipping_cost: Money,
+    total_shipping_cost_per_pound: f64,
+}
+
+impl Counter {
+    fn new() -> Self {
+        Counter {
+            total_retail_cost: Money::ZERO,
+            total_item_count: 0,
+            total_shipping_cost: Money::ZERO,
+            total_shipping_cost_per_pound: 0.0,
+        }
+    }
+
+    fn update(&mut self, order: &Order) {
+        self.total_retail_cost += order.retail_value;
+        self.total_item_count += 1;
+        self.total_shipping_cost += order.shipping_cost;
+        self.total_shipping_cost_per_pound += order.shipping_cost_per_pound;
+    }
+
+    /// Shipping spend per dollar of retail value, or `None` when the retail
+    /// value of every counted order is zero.
+    fn cost_per_dollar(&self) -> Option<f64> {
+        self.total_shipping_cost.ratio(self.total_retail_cost)
+    }
+
+    fn avg_shipping_cost_per_pound(&self) -> f64 {
+        self.total_shipping_cost_per_pound / f64::from(self.total_item_count)
+    }
+
+    fn avg_shipping_cost(&self) -> f64 {
+        self.total_shipping_cost.to_f64() / f64::from(self.total_item_count)
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct AverageOutput {
+    pub region: Region,
+    pub label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shipping_method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub carrier: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duties: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country_group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    /// Empty for orders without a state and for military addresses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_fips: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+    /// "chart" or "estimate"; empty on rows without a zone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone_source: Option<String>,
+    #[serde(serialize_with = "money::serialize_rounded")]
+    pub avg: f64,
+}
+
+impl AverageOutput {
+    fn new(key: &GroupKey, label: String, avg: f64) -> AverageOutput {
+        AverageOutput {
+            region: key.region.clone(),
+            label,
+            shipping_method: key.shipping_method.as_ref().map(ShippingMethod::name),
+            carrier: key.carrier.as_ref().map(Carrier::name),
+            product: key.product.clone(),
+            scope: key.scope.as_ref().map(Scope::name),
+            duties: key.duties.as_ref().map(DutiesTerm::name),
+            package: key.package.as_ref().map(PackageType::name),
+            country: key.country.clone(),
+            country_group: key.country_group.clone(),
+            state: key
+                .state
+                .map(|state| state.map_or_else(|| String::from("Unknown"), |s| s.name())),
+            state_fips: key
+                .state
+                .map(|state| state.and_then(|s| s.fips()).unwrap_or_default()),
+            zone: key
+                .zone
+                .map(|zone| zone.map_or_else(|| String::from("Unknown"), |zone| zone.to_string())),
+            zone_source: key
+                .zone_source
+                .map(|source| source.as_ref().map(ZoneSource::name).unwrap_or_default()),
+            avg,
+        }
+    }
+}
+
+/// Tier averages are reported for Economy and Ground only. Carrier and
+/// product averages cover every tier the carrier shipped, so a carrier's
+/// figure isn't just its cheapest services.
+fn report_shipping_method(level: Level, shipping_method: &ShippingMethod) -> bool {
+    match level {
+        Level::Tier => matches!(
+            shipping_method,
+            ShippingMethod::Economy | ShippingMethod::Ground
+        ),
+        Level::Carrier | Level::Product => !matches!(
+            shipping_method,
+            ShippingMethod::Unknown | ShippingMethod::Error
+        ),
+    }
+}
+
+pub struct Averages {
+    level: Level,
+    area: Area,
+    group_by: Vec<Dimension>,
+    rate_counter: HashMap<GroupKey, Counter>,
+    shipping_rate_counter: HashMap<(GroupKey, WeightRange), Counter>,
+}
+
+impl Averages {
+    pub fn new(level: Level, area: Area, group_by: Vec<Dimension>) -> Averages {
+        Averages {
+            level,
+            area,
+            group_by,
+            rate_counter: HashMap::new(),
+            shipping_rate_counter: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, order: &Order) {
+        if !report_shipping_method(self.level, &order.shipping_method) {
+            return;
+        }
+        // International services are never averaged with domestic ones, even
+        // when the address is in a US region.
+        if order.scope == Scope::International {
+            self.add_to_region(order, Region::International, false);
+            return;
+        }
+        match (self.area, order.zone) {
+            (Area::Zone, Some(zone)) => {
+                self.add_to_region(order, Region::Named(format!("Zone {}", zone)), true)
+            }
+            _ => self.add_to_region(order, order.region.clone(), false),
+        }
+
+        if !order.state.is_some_and(|state| state.in_all_regions()) {
+            return;
+        }
+
+        // Region::All is for continental US
+        self.add_to_region(order, Region::All, false);
+    }
+
+    /// `zoned` when `region` is the order's zone rather than its region.
+    fn add_to_region(&mut self, order: &Order, region: Region, zoned: bool) {
+        let mut key = GroupKey::new(order, region, self.level, &self.group_by);
+        let has_zone_dimension = self.group_by.contains(&Dimension::Zone);
+        if self.area == Area::Zone || has_zone_dimension {
+            key.zone_source = Some(order.zone_source.filter(|_| zoned || has_zone_dimension));
+        }
+
+        self.rate_counter
+            .entry(key.clone())
+            .or_insert_with(Counter::new)
+            .update(order);
+
+        self.shipping_rate_counter
+            .entry((key, order.ship_weight.clone()))
+            .or_insert_with(Counter::new)
+            .update(order);
+    }
+
+    pub fn outputs(&self) -> Vec<AverageOutput> {
+        let mut avgs: Vec<AverageOutput> = vec![];
+
+        for (key, counter) in &self.rate_counter {
+            if let Some(cost_per_dollar) = counter.cost_per_dollar() {
+                avgs.push(AverageOutput::new(
+                    key,
+                    "Cost per $".to_string(),
+                    cost_per_dollar,
+                ));
+            }
+            avgs.push(AverageOutput::new(
+                key,
+                "$ per Pound".to_string(),
+                counter.avg_shipping_cost_per_pound(),
+            ));
+        }
+
+        for ((key, weight_range), counter) in &self.shipping_rate_counter {
+            avgs.push(AverageOutput::new(
+                key,
+                weight_range.name(),
+                counter.avg_shipping_cost(),
+            ));
+        }
+
+        avgs.sort_by_key(|k| {
+            (
+                k.region.name(),
+                k.shipping_method.clone(),
+                k.carrier.clone(),
+                k.product.clone(),
+                k.scope.clone(),
+                k.duties.clone(),
+                k.package.clone(),
+                k.country_group.clone(),
+                k.country.clone(),
+                k.state.clone(),
+                (k.zone.clone(), k.zone_source.clone()),
+                k.label.clone(),
+            )
+        });
+        avgs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OrderFromCSVInput;
+
+    fn avg(outputs: &[AverageOutput], region: Region, carrier: Option<&str>, label: &str) -> f64 {
+        outputs
+            .iter()
+            .find(|o| o.region == region && o.carrier.as_deref() == carrier && o.label == label)
+            .map(|o| o.avg)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_every_order_is_counted() {
+        let mut averages = Averages::new(Level::Tier, Area::Region, vec![]);
+        averages.add(&OrderFromCSVInput::row("10016", "FedEx Ground", "1", "10", "100").to_order());
+        averages.add(
+            &OrderFromCSVInput::row("10017", "UPS Ground [RESA]", "1", "20", "100").to_order(),
+        );
+        let outputs = averages.outputs();
+        assert_eq!(avg(&outputs, Region::Northeast, None, "Cost per $"), 0.15);
+        assert_eq!(avg(&outputs, Region::All, None, "$ per Pound"), 15.0);
+        assert!(outputs.iter().all(|o| o.carrier.is_none()));
+    }
+
+    #[test]
+    fn test_group_by_carrier() {
+        let mut averages = Averages::new(
+            Level::Tier,
+            Area::Region,
+            Dimension::list_from_str("carrier").unwrap(),
+        );
+        averages.add(&OrderFromCSVInput::row("60601", "FedEx Ground", "1", "10", "100").to_order());
+        averages.add(
+            &OrderFromCSVInput::row("60602", "UPS Ground [RESA]", "1", "20", "100").to_order(),
+        );
+        let outputs = averages.outputs();
+        assert_eq!(
+            avg(&outputs, Region::Midwest, Some("FedEx"), "Cost per $"),
+            0.1
+        );
+        assert_eq!(
+            avg(&outputs, Region::Midwest, Some("UPS"), "Cost per $"),
+            0.2
+        );
+        assert!(Dimension::list_from_str("carrier,colour").is_err());
+    }
+
+    #[test]
+    fn test_group_by_scope_and_duties() {
+        let mut averages = Averages::new(
+            Level::Tier,
+            Area::Region,
+            Dimension::list_from_str("scope, duties").unwrap(),
+        );
+        averages.add(&OrderFromCSVInput::row("60601", "UPS SurePost", "1", "10", "100").to_order());
+        averages.add(
+            &OrderFromCSVInput::row(
+                "60602",
+                "UPS Worldwide Saver (Duties Not Paid)",
+                "1",
+                "40",
+                "100",
+            )
+            .to_order(),
+        );
+        let outputs = averages.outputs();
+        let scoped: Vec<(Region, Option<&str>, Option<&str>, f64)> = outputs
+            .iter()
+            .filter(|o| o.region != Region::All && o.label == "Cost per $")
+            .map(|o| {
+                (
+                    o.region.clone(),
+                    o.scope.as_deref(),
+                    o.duties.as_deref(),
+                    o.avg,
+                )
+            })
+            .collect();
+        assert_eq!(
+            scoped,
+            vec![
+                (
+                    Region::International,
+                    Some("International"),
+                    Some("DDU"),
+                    0.4
+                ),
+                (Region::Midwest, Some("Domestic"), Some("Unknown"), 0.1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_international_services_stay_out_of_domestic_regions() {
+        let mut averages = Averages::new(Level::Tier, Area::Region, vec![]);
+        averages.add(&OrderFromCSVInput::row("60601", "UPS SurePost", "1", "10", "100").to_order());
+        let service = "UPS Worldwide Saver (Duties Not Paid)";
+        averages.add(&OrderFromCSVInput::row("60602", service, "1", "40", "100").to_order());
+        let outputs = averages.outputs();
+        assert_eq!(avg(&outputs, Region::Midwest, None, "Cost per $"), 0.1);
+        assert_eq!(avg(&outputs, Region::All, None, "Cost per $"), 0.1);
+        assert_eq!(
+            avg(&outputs, Region::International, None, "Cost per $"),
+            0.4
+        );
+    }
+
+    #[test]
+    fn test_roll_up_and_drill_down() {
+        let orders = [
+            OrderFromCSVInput::row("60601", "FedEx Ground", "1", "10", "100").to_order(),
+            OrderFromCSVInput::row("60602", "FedEx Home Delivery", "1", "30", "100").to_order(),
+            OrderFromCSVInput::row("60603", "UPS SurePost", "1", "20", "100").to_order(),
+            OrderFromCSVInput::row("60604", "FedEx 2nd Day", "1", "50", "100").to_order(),
+        ];
+        let cost_per_dollar = |level: Level| -> Vec<(Option<String>, Option<String>, f64)> {
+            let mut averages = Averages::new(level, Area::Region, vec![]);
+            for order in &orders {
+                averages.add(order);
+            }
+            averages
+                .outputs()
+                .into_iter()
+                .filter(|o| o.region == Region::Midwest && o.label == "Cost per $")
+                .map(|o| (o.carrier, o.product, o.avg))
+                .collect()
+        };
+
+        assert_eq!(
+            cost_per_dollar(Level::Carrier),
+            vec![
+                (Some("FedEx".to_string()), None, 0.3),
+                (Some("UPS".to_string()), None, 0.2),
+            ]
+        );
+        assert_eq!(
+            cost_per_dollar(Level::Product),
+            vec![
+                (
+                    Some("UPS".to_string()),
+                    Some("ups surepost".to_string()),
+                    0.2
+                ),
+                (
+                    Some("FedEx".to_string()),
+                    Some("fedex ground".to_string()),
+                    0.1
+                ),
+                (
+                    Some("FedEx".to_string()),
+                    Some("fedex home delivery".to_string()),
+                    0.3
+                ),
+                (
+                    Some("FedEx".to_string()),
+                    Some("fedex 2nd day".to_string()),
+                    0.5
+                ),
+            ]
+        );
+        assert_eq!(cost_per_dollar(Level::Tier).len(), 2);
+        assert!(Level::from_name("region").is_err());
+    }
+
+    #[test]
+    fn test_international_breakdown_by_country_and_group() {
+        let abroad = |country: &str, label_cost: &str| {
+            let service = "UPS Worldwide Saver (Duties Not Paid)";
+            let input = OrderFromCSVInput {
+                country: country.to_string(),
+                ..OrderFromCSVInput::row("", service, "1", label_cost, "100")
+            };
+            input.to_order()
+        };
+        let mut averages = Averages::new(
+            Level::Tier,
+            Area::Region,
+            Dimension::list_from_str("country_group,country").unwrap(),
+        );
+        averages.add(&abroad("FR", "30"));
+        averages.add(&abroad("DE", "40"));
+        averages.add(&abroad("CA", "20"));
+        let outputs = averages.outputs();
+        let rows: Vec<(&str, &str, f64)> = outputs
+            .iter()
+            .filter(|o| o.label == "Cost per $")
+            .map(|o| {
+                (
+                    o.country_group.as_deref().unwrap(),
+                    o.country.as_deref().unwrap(),
+                    o.avg,
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("Canada", "Canada", 0.2),
+                ("EU", "France", 0.3),
+                ("EU", "Germany", 0.4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_by_state() {
+        let mut averages = Averages::new(
+            Level::Tier,
+            Area::Region,
+            Dimension::list_from_str("state").unwrap(),
+        );
+        averages.add(&OrderFromCSVInput::row("33101", "UPS SurePost", "1", "30", "100").to_order());
+        averages.add(&OrderFromCSVInput::row("30301", "UPS SurePost", "1", "10", "100").to_order());
+        averages.add(&OrderFromCSVInput::row("30302", "UPS SurePost", "1", "20", "100").to_order());
+        let outputs = averages.outputs();
+        let rows: Vec<(&str, &str, f64)> = outputs
+            .iter()
+            .filter(|o| o.region == Region::Southeast && o.label == "Cost per $")
+            .map(|o| {
+                (
+                    o.state.as_deref().unwrap(),
+                    o.state_fips.as_deref().unwrap(),
+                    o.avg,
+                )
+            })
+            .collect();
+        assert_eq!(rows, vec![("Florida", "12", 0.3), ("Georgia", "13", 0.15)]);
+    }
+
+    #[test]
+    fn test_group_by_zone() {
+        let mut averages = Averages::new(
+            Level::Tier,
+            Area::Region,
+            Dimension::list_from_str("zone").unwrap(),
+        );
+        let zoned = |zip: &str, label_cost: &str, zone: Option<u8>| {
+            let mut order =
+                OrderFromCSVInput::row(zip, "UPS SurePost", "1", label_cost, "100").to_order();
+            order.zone = zone;
+            order
+        };
+        averages.add(&zoned("33101", "30", Some(5)));
+        averages.add(&zoned("30301", "10", Some(5)));
+        averages.add(&zoned("60601", "20", Some(4)));
+        averages.add(&zoned("60602", "40", None));
+        let outputs = averages.outputs();
+        let rows: Vec<(&str, f64)> = outputs
+            .iter()
+            .filter(|o| o.region == Region::All && o.label == "Cost per $")
+            .map(|o| (o.zone.as_deref().unwrap(), o.avg))
+            .collect();
+        assert_eq!(rows, vec![("4", 0.2), ("5", 0.2), ("Unknown", 0.4)]);
+    }
+
+    #[test]
+    fn test_zone_instead_of_region() {
+        let mut averages = Averages::new(Level::Tier, Area::Zone, vec![]);
+        let zoned = |zip: &str, label_cost: &str, zone: Option<u8>| {
+            let mut order =
+                OrderFromCSVInput::row(zip, "UPS SurePost", "1", label_cost, "100").to_order();
+            order.zone = zone;
+            order.zone_source = zone.and(Some(ZoneSource::Estimate));
+            order
+        };
+        averages.add(&zoned("33101", "30", Some(5)));
+        averages.add(&zoned("60601", "10", Some(5)));
+        averages.add(&zoned("60602", "20", Some(4)));
+        averages.add(&zoned("09001", "40", None));
+        let outputs = averages.outputs();
+        let rows: Vec<(String, Option<&str>, f64)> = outputs
+            .iter()
+            .filter(|o| o.label == "Cost per $")
+            .map(|o| (o.region.name(), o.zone_source.as_deref(), o.avg))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (String::from("All Regions"), Some(""), 0.2),
+                (String::from("Military"), Some(""), 0.4),
+                (String::from("Zone 4"), Some("estimate"), 0.2),
+                (String::from("Zone 5"), Some("estimate"), 0.2),
+            ]
+        );
+    }
+}
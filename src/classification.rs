@@ -0,0 +1,522 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::{fmt, fs};
+
+use crate::normalize::ServiceName;
+use crate::{Carrier, DutiesTerm, PackageType, Scope, ShippingMethod, UnexpectedError};
+
+// CARRIER SERVICE CLASSIFICATION
+//
+// "Carrier Service" strings are mapped to a `ShippingMethod` by an ordered list
+// of rules. The built-in table ships with the binary; a rules file can add to
+// it so new 3PL service names don't need a recompile. Rules are tried against
+// the raw string first, then against its canonical key (see `normalize`), so
+// most rules only need the key and a raw rule can still single out one tagged
+// variant.
+
+pub const DEFAULT_RULES_PATH: &str = "service_rules.json";
+
+const BUILT_IN_RULES: &str = include_str!("service_rules.json");
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Exact,
+    CaseInsensitive,
+    /// Case-insensitive prefix.
+    Prefix,
+    Regex,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Rule {
+    #[serde(rename = "match")]
+    pub kind: MatchKind,
+    pub pattern: String,
+    pub tier: ShippingMethod,
+    /// Overrides the carrier detected from the service name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub carrier: Option<Carrier>,
+    /// Overrides the scope detected from the service name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Scope>,
+    /// Overrides the duties term detected from the service name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duties: Option<DutiesTerm>,
+    /// Overrides the package type detected from the service name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<PackageType>,
+    /// The carrier's product the service belongs to. Defaults to the
+    /// canonical key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    /// Things about the service other than its speed, e.g. "jewelry".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+    /// Higher priorities are tried first. Rules with equal priority are tried
+    /// in file order, rules files before the built-in table.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub priority: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+fn is_zero(priority: &i32) -> bool {
+    *priority == 0
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct RulesFile {
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Exact(String),
+    CaseInsensitive(String),
+    Prefix(String),
+    Regex(Regex),
+}
+
+impl Matcher {
+    fn new(rule: &Rule) -> Result<Matcher, Box<dyn Error>> {
+        Ok(match rule.kind {
+            MatchKind::Exact => Matcher::Exact(rule.pattern.clone()),
+            MatchKind::CaseInsensitive => Matcher::CaseInsensitive(rule.pattern.to_lowercase()),
+            MatchKind::Prefix => Matcher::Prefix(rule.pattern.to_lowercase()),
+            MatchKind::Regex => {
+                Matcher::Regex(Regex::new(&rule.pattern).map_err(|err| UnexpectedError {
+                    message: format!("Invalid regex rule \"{}\": {}", rule.pattern, err),
+                })?)
+            }
+        })
+    }
+
+    fn is_match(&self, service: &str) -> bool {
+        match self {
+            Matcher::Exact(pattern) => service == pattern,
+            Matcher::CaseInsensitive(pattern) => service.to_lowercase() == *pattern,
+            Matcher::Prefix(pattern) => service.to_lowercase().starts_with(pattern.as_str()),
+            Matcher::Regex(regex) => regex.is_match(service),
+        }
+    }
+
+    /// A string this matcher accepts, used to test overlap with regex rules.
+    fn example(&self) -> Option<&str> {
+        match self {
+            Matcher::Exact(pattern)
+            | Matcher::CaseInsensitive(pattern)
+            | Matcher::Prefix(pattern) => Some(pattern),
+            Matcher::Regex(_) => None,
+        }
+    }
+
+    /// Whether every string `other` matches is also matched by `self`. Regex
+    /// rules are only compared by their literal examples, so this can miss
+    /// some containments but never reports a false one for literal rules.
+    fn contains(&self, other: &Matcher) -> bool {
+        match (self, other) {
+            (Matcher::Exact(a), Matcher::Exact(b)) => a == b,
+            (Matcher::CaseInsensitive(a), Matcher::Exact(b))
+            | (Matcher::CaseInsensitive(a), Matcher::CaseInsensitive(b)) => *a == b.to_lowercase(),
+            (Matcher::Prefix(a), Matcher::Exact(b))
+            | (Matcher::Prefix(a), Matcher::CaseInsensitive(b))
+            | (Matcher::Prefix(a), Matcher::Prefix(b)) => b.to_lowercase().starts_with(a.as_str()),
+            (Matcher::Regex(a), Matcher::Exact(b)) => a.is_match(b),
+            (Matcher::Regex(a), Matcher::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+
+    /// Whether some string is matched by both `self` and `other`.
+    fn overlaps(&self, other: &Matcher) -> bool {
+        if self.contains(other) || other.contains(self) {
+            return true;
+        }
+        match (self, other) {
+            (Matcher::Regex(regex), literal) | (literal, Matcher::Regex(regex)) => literal
+                .example()
+                .is_some_and(|example| regex.is_match(example)),
+            (Matcher::Prefix(a), Matcher::CaseInsensitive(b))
+            | (Matcher::CaseInsensitive(b), Matcher::Prefix(a)) => b.starts_with(a.as_str()),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ServiceRules {
+    /// Rules in the order they are tried.
+    rules: Vec<(Rule, Matcher)>,
+}
+
+impl ServiceRules {
+    pub fn new(rules: Vec<Rule>) -> Result<ServiceRules, Box<dyn Error>> {
+        let mut compiled = rules
+            .into_iter()
+            .map(|rule| Matcher::new(&rule).map(|matcher| (rule, matcher)))
+            .collect::<Result<Vec<_>, _>>()?;
+        // A stable sort keeps file order within a priority.
+        compiled.sort_by_key(|(rule, _)| -rule.priority);
+        Ok(ServiceRules { rules: compiled })
+    }
+
+    pub fn built_in_rules() -> Vec<Rule> {
+        let file: RulesFile =
+            serde_json::from_str(BUILT_IN_RULES).expect("built-in service rules are valid JSON");
+        file.rules
+    }
+
+    #[cfg(test)]
+    pub fn built_in() -> ServiceRules {
+        ServiceRules::new(ServiceRules::built_in_rules()).expect("built-in service rules compile")
+    }
+
+    /// The rules in the file at `path` followed by the built-in table. A
+    /// missing file is only an error when it isn't the default path.
+    pub fn load(path: &str) -> Result<ServiceRules, Box<dyn Error>> {
+        ServiceRules::with_file(read_rules_file(path)?)
+    }
+
+    /// The rules in `file` followed by the built-in table.
+    pub fn with_file(file: RulesFile) -> Result<ServiceRules, Box<dyn Error>> {
+        let mut rules = file.rules;
+        rules.extend(ServiceRules::built_in_rules());
+        ServiceRules::new(rules)
+    }
+
+    /// The rules in the order they are tried.
+    pub fn rules(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter().map(|(rule, _)| rule)
+    }
+
+    pub fn rule_for(&self, service: &str) -> Option<&Rule> {
+        let find = |candidate: &str| {
+            self.rules
+                .iter()
+                .find(|(_, matcher)| matcher.is_match(candidate))
+                .map(|(rule, _)| rule)
+        };
+        find(service).or_else(|| find(&ServiceName::parse(service).key))
+    }
+
+    /// The tier for `service`, or `ShippingMethod::Error` when no rule
+    /// matches.
+    pub fn tier(&self, service: &str) -> ShippingMethod {
+        self.rule_for(service)
+            .map(|rule| rule.tier.clone())
+            .unwrap_or(ShippingMethod::Error)
+    }
+
+    pub fn classify(&self, service: &str) -> Classification {
+        let rule = self.rule_for(service);
+        let key = ServiceName::parse(service).key;
+        Classification {
+            tier: self.tier(service),
+            carrier: rule
+                .and_then(|rule| rule.carrier.clone())
+                .unwrap_or_else(|| Carrier::from_service_key(&key)),
+            scope: rule
+                .and_then(|rule| rule.scope.clone())
+                .unwrap_or_else(|| Scope::from_service_key(&key)),
+            duties: rule
+                .and_then(|rule| rule.duties.clone())
+                .unwrap_or_else(|| DutiesTerm::from_service_key(&key)),
+            package: rule
+                .and_then(|rule| rule.package.clone())
+                .unwrap_or_else(|| PackageType::from_service_key(&key)),
+            product: rule
+                .and_then(|rule| rule.product.clone())
+                .unwrap_or_else(|| key.clone()),
+            flags: rule.map(|rule| rule.flags.clone()).unwrap_or_default(),
+        }
+    }
+
+    /// Rules that can never match because an earlier rule always wins, and
+    /// pairs of rules that can match the same string but disagree on the tier.
+    pub fn validate(&self) -> Vec<RuleProblem> {
+        let mut problems = vec![];
+        for (index, (rule, matcher)) in self.rules.iter().enumerate() {
+            let earlier = &self.rules[..index];
+            if let Some((winner, _)) = earlier.iter().find(|(_, m)| m.contains(matcher)) {
+                problems.push(RuleProblem::Shadowed {
+                    rule: rule.clone(),
+                    by: winner.clone(),
+                });
+                continue;
+            }
+            for (other, other_matcher) in earlier {
+                if other.tier != rule.tier && other_matcher.overlaps(matcher) {
+                    problems.push(RuleProblem::Overlap {
+                        rule: rule.clone(),
+                        with: other.clone(),
+                    });
+                }
+            }
+        }
+        problems
+    }
+}
+
+/// Everything classification says about one "Carrier Service" value.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Classification {
+    pub tier: ShippingMethod,
+    pub carrier: Carrier,
+    pub scope: Scope,
+    pub duties: DutiesTerm,
+    pub package: PackageType,
+    pub product: String,
+    pub flags: Vec<String>,
+}
+
+pub fn read_rules_file(path: &str) -> Result<RulesFile, Box<dyn Error>> {
+    match crate::read_optional(path, DEFAULT_RULES_PATH)? {
+        Some(contents) => Ok(serde_json::from_str(&contents)?),
+        None => Ok(RulesFile::default()),
+    }
+}
+
+pub fn write_rules_file(path: &str, file: &RulesFile) -> Result<(), Box<dyn Error>> {
+    let json_string = serde_json::to_string_pretty(file)?;
+    fs::write(path, json_string + "\n")?;
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RuleProblem {
+    Shadowed { rule: Rule, by: Rule },
+    Overlap { rule: Rule, with: Rule },
+}
+
+fn describe(rule: &Rule) -> String {
+    format!(
+        "{:?} \"{}\" => {} (priority {})",
+        rule.kind,
+        rule.pattern,
+        rule.tier.name(),
+        rule.priority
+    )
+}
+
+impl fmt::Display for RuleProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleProblem::Shadowed { rule, by } => {
+                write!(
+                    f,
+                    "Shadowed: {} never matches, {} wins first",
+                    describe(rule),
+                    describe(by)
+                )
+            }
+            RuleProblem::Overlap { rule, with } => {
+                write!(
+                    f,
+                    "Overlap: {} and {} match the same service",
+                    describe(rule),
+                    describe(with)
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(kind: MatchKind, pattern: &str, tier: ShippingMethod, priority: i32) -> Rule {
+        Rule {
+            kind,
+            pattern: pattern.to_string(),
+            tier,
+            carrier: None,
+            scope: None,
+            duties: None,
+            package: None,
+            product: None,
+            flags: vec![],
+            priority,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_built_in_table() {
+        let rules = ServiceRules::built_in();
+        assert_eq!(rules.tier("UPS SurePost"), ShippingMethod::Economy);
+        assert_eq!(rules.tier("UPS Ground [RESA]"), ShippingMethod::Ground);
+        assert_eq!(rules.tier("FedEx 2nd Day [RESA]"), ShippingMethod::Express);
+        assert_eq!(rules.tier("FedEx 2nd Day"), ShippingMethod::Priority);
+        assert_eq!(
+            rules.tier("FedEx One Rate (Pak) 2-Day [RESA JEWELRY]"),
+            ShippingMethod::Express
+        );
+        assert_eq!(rules.tier("fedex"), ShippingMethod::Unknown);
+        assert_eq!(rules.tier("Carrier Pigeon"), ShippingMethod::Error);
+        assert!(rules.validate().is_empty());
+    }
+
+    #[test]
+    fn test_variants_fall_back_to_the_canonical_key() {
+        let rules = ServiceRules::built_in();
+        assert_eq!(rules.tier("UPS  SUREPOST [RESA]"), ShippingMethod::Economy);
+        assert_eq!(rules.tier("FedEx Ground [RESA]"), ShippingMethod::Ground);
+        assert_eq!(
+            rules.tier("USPS First-Class Mail [Order Protection Highly Recommended]"),
+            ShippingMethod::Economy
+        );
+        assert_eq!(
+            rules.tier(
+                "USPS First-Class Mail [Route Protection Highly Recommended Not Responsible For Lost Shipment]"
+            ),
+            ShippingMethod::Unknown
+        );
+    }
+
+    #[test]
+    fn test_match_kinds_and_priority() {
+        let rules = ServiceRules::new(vec![
+            rule(MatchKind::Prefix, "ups ground", ShippingMethod::Ground, 0),
+            rule(
+                MatchKind::CaseInsensitive,
+                "ups next day air",
+                ShippingMethod::Expedited,
+                0,
+            ),
+            rule(
+                MatchKind::Regex,
+                r"(?i)\bintl\b",
+                ShippingMethod::Priority,
+                0,
+            ),
+            rule(
+                MatchKind::Exact,
+                "UPS Ground Saver",
+                ShippingMethod::Economy,
+                10,
+            ),
+        ])
+        .unwrap();
+        assert_eq!(rules.tier("UPS Ground Saver"), ShippingMethod::Economy);
+        assert_eq!(rules.tier("UPS GROUND [RESA]"), ShippingMethod::Ground);
+        assert_eq!(rules.tier("UPS Next Day AIR"), ShippingMethod::Expedited);
+        assert_eq!(rules.tier("FedEx Intl Economy"), ShippingMethod::Priority);
+    }
+
+    #[test]
+    fn test_validate_reports_shadowed_and_overlapping_rules() {
+        let rules = ServiceRules::new(vec![
+            rule(MatchKind::Prefix, "ups", ShippingMethod::Ground, 0),
+            rule(MatchKind::Exact, "UPS SurePost", ShippingMethod::Economy, 0),
+            rule(MatchKind::Regex, "^FedEx", ShippingMethod::Ground, 0),
+            rule(
+                MatchKind::Exact,
+                "FedEx 2nd Day",
+                ShippingMethod::Express,
+                5,
+            ),
+        ])
+        .unwrap();
+        let problems = rules.validate();
+        assert_eq!(problems.len(), 2);
+        assert!(matches!(
+            &problems[0],
+            RuleProblem::Shadowed { rule, .. } if rule.pattern == "UPS SurePost"
+        ));
+        assert!(matches!(
+            &problems[1],
+            RuleProblem::Overlap { rule, .. } if rule.pattern == "^FedEx"
+        ));
+    }
+
+    #[test]
+    fn test_carrier_is_detected_or_taken_from_the_rule() {
+        let mut rules = ServiceRules::built_in_rules();
+        rules.insert(
+            0,
+            Rule {
+                carrier: Some(Carrier::Dhl),
+                ..rule(
+                    MatchKind::Exact,
+                    "Misc Transfer Carrier",
+                    ShippingMethod::Unknown,
+                    0,
+                )
+            },
+        );
+        let rules = ServiceRules::new(rules).unwrap();
+        assert_eq!(rules.classify("UPS SUREPOST").carrier, Carrier::Ups);
+        assert_eq!(rules.classify("FEDEx").carrier, Carrier::FedEx);
+        assert_eq!(
+            rules.classify("Upgrade to (3-5 Day) DHL Expedited").carrier,
+            Carrier::Dhl
+        );
+        assert_eq!(rules.classify("Carrier Pigeon").carrier, Carrier::Other);
+        assert_eq!(
+            rules.classify("Misc Transfer Carrier").carrier,
+            Carrier::Dhl
+        );
+    }
+
+    #[test]
+    fn test_scope_and_duties_are_detected() {
+        let rules = ServiceRules::built_in();
+        let classification = rules.classify("FedEx Intl Priority (Envelope) (Duties Not Paid)");
+        assert_eq!(classification.scope, Scope::International);
+        assert_eq!(classification.duties, DutiesTerm::Ddu);
+
+        let classification = rules.classify("DHL International");
+        assert_eq!(classification.scope, Scope::International);
+        assert_eq!(classification.duties, DutiesTerm::Unknown);
+
+        let classification = rules.classify("UPS Worldwide Saver DDP");
+        assert_eq!(classification.scope, Scope::International);
+        assert_eq!(classification.duties, DutiesTerm::Ddp);
+
+        assert_eq!(rules.classify("UPS Ground").scope, Scope::Domestic);
+    }
+
+    #[test]
+    fn test_package_type_is_detected() {
+        let rules = ServiceRules::built_in();
+        let package = |service: &str| rules.classify(service).package;
+        assert_eq!(
+            package("FedEx One Rate (Pak) 2-Day [RESA JEWELRY]"),
+            PackageType::Pak
+        );
+        assert_eq!(
+            package("FedEx Standard Overnight (Envelope)"),
+            PackageType::Envelope
+        );
+        assert_eq!(
+            package("USPS Priority Mail Flat Rate Box"),
+            PackageType::Box
+        );
+        assert_eq!(package("FedEx Standard Overnight"), PackageType::Unknown);
+    }
+
+    #[test]
+    fn test_special_cases_are_flags_on_a_product() {
+        let rules = ServiceRules::built_in();
+        let jewelry = rules.classify("FedEx One Rate (Pak) 2-Day [RESA JEWELRY]");
+        let plain = rules.classify("FedEx One Rate (Pak) 2-Day");
+        assert_eq!(jewelry.product, plain.product);
+        assert_eq!(jewelry.tier, plain.tier);
+        assert_eq!(jewelry.flags, vec!["jewelry".to_string()]);
+        assert!(plain.flags.is_empty());
+
+        let transfer = rules.classify("Misc Transfer Carrier");
+        assert_eq!(transfer.tier, ShippingMethod::Unknown);
+        assert_eq!(transfer.flags, vec!["transfer".to_string()]);
+    }
+
+    #[test]
+    fn test_invalid_regex_is_an_error() {
+        let result =
+            ServiceRules::new(vec![rule(MatchKind::Regex, "(", ShippingMethod::Ground, 0)]);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use std::error::Error;
+use std::io::{BufRead, Write};
+
+use crate::classification::{MatchKind, Rule};
+use crate::normalize::ServiceName;
+use crate::suggest::Suggestion;
+use crate::unmapped::UnmappedService;
+use crate::ShippingMethod;
+
+// INTERACTIVE CLASSIFICATION
+//
+// Walks the unrecognised services of an input file, proposes a tier for each
+// and lets the operator accept or override it. Every decision becomes an
+// exact rule on the service's canonical key.
+
+const TIER_CHOICES: [ShippingMethod; 6] = [
+    ShippingMethod::Economy,
+    ShippingMethod::Ground,
+    ShippingMethod::Expedited,
+    ShippingMethod::Express,
+    ShippingMethod::Priority,
+    ShippingMethod::Unknown,
+];
+
+/// A first guess at the tier from words that usually give the speed away,
+/// for services with nothing in common with a known one.
+pub fn propose_tier(key: &str) -> Option<ShippingMethod> {
+    let hints = [
+        ("overnight", ShippingMethod::Expedited),
+        ("next day", ShippingMethod::Expedited),
+        ("express", ShippingMethod::Expedited),
+        ("2nd day", ShippingMethod::Express),
+        ("2-day", ShippingMethod::Express),
+        ("priority", ShippingMethod::Priority),
+        ("ground", ShippingMethod::Ground),
+        ("home delivery", ShippingMethod::Ground),
+        ("surepost", ShippingMethod::Economy),
+        ("first-class", ShippingMethod::Economy),
+        ("parcel", ShippingMethod::Economy),
+        ("saver", ShippingMethod::Economy),
+        ("economy", ShippingMethod::Economy),
+    ];
+    hints
+        .iter()
+        .find(|(hint, _)| key.contains(hint))
+        .map(|(_, tier)| tier.clone())
+}
+
+enum Answer {
+    Tier(ShippingMethod),
+    Skip,
+    Quit,
+}
+
+fn parse_answer(answer: &str, proposal: &Option<ShippingMethod>) -> Option<Answer> {
+    match answer.trim().to_lowercase().as_str() {
+        "" => proposal.clone().map(Answer::Tier),
+        "s" | "skip" => Some(Answer::Skip),
+        "q" | "quit" => Some(Answer::Quit),
+        other => TIER_CHOICES
+            .iter()
+            .find(|tier| tier.name().to_lowercase() == other)
+            .map(|tier| Answer::Tier(tier.clone())),
+    }
+}
+
+/// Prompts for each of `services` on `output`, reading answers from `input`.
+/// `save` is called with each new rule as soon as it is decided, so quitting
+/// part way keeps the decisions made so far. Returns the number of rules.
+pub fn classify_interactively<R, W, S>(
+    services: &[UnmappedService],
+    mut input: R,
+    mut output: W,
+    mut save: S,
+) -> Result<usize, Box<dyn Error>>
+where
+    R: BufRead,
+    W: Write,
+    S: FnMut(Rule) -> Result<(), Box<dyn Error>>,
+{
+    let choices: Vec<String> = TIER_CHOICES
+        .iter()
+        .map(|tier| tier.name().to_lowercase())
+        .collect();
+    let mut saved = 0;
+
+    for (index, service) in services.iter().enumerate() {
+        let key = ServiceName::parse(&service.service).key;
+        let proposal = service
+            .suggestion
+            .as_ref()
+            .map(|suggestion| suggestion.tier.clone())
+            .or_else(|| propose_tier(&key));
+        writeln!(
+            output,
+            "\n[{}/{}] \"{}\" ({} rows, ${} label spend, e.g. zips {})",
+            index + 1,
+            services.len(),
+            service.service,
+            service.rows,
+            service.label_spend,
+            service.sample_zips.join(", ")
+        )?;
+        if let Some(Suggestion {
+            confidence,
+            similar_to,
+            ..
+        }) = &service.suggestion
+        {
+            writeln!(
+                output,
+                "Looks like \"{}\" ({:.0}% similar).",
+                similar_to,
+                confidence * 100.0
+            )?;
+        }
+
+        let answer = loop {
+            match &proposal {
+                Some(tier) => write!(output, "Tier [{}]", tier.name())?,
+                None => write!(output, "Tier")?,
+            }
+            write!(output, " ({}, s to skip, q to quit): ", choices.join("/"))?;
+            output.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                break Answer::Quit;
+            }
+            match parse_answer(&line, &proposal) {
+                Some(answer) => break answer,
+                None => writeln!(output, "Please answer with one of the choices.")?,
+            }
+        };
+
+        match answer {
+            Answer::Tier(tier) => {
+                save(Rule {
+                    kind: MatchKind::Exact,
+                    pattern: key,
+                    tier,
+                    carrier: None,
+                    scope: None,
+                    duties: None,
+                    package: None,
+                    product: None,
+                    flags: vec![],
+                    priority: 0,
+                    note: Some(format!("Classified from \"{}\".", service.service)),
+                })?;
+                saved += 1;
+            }
+            Answer::Skip => continue,
+            Answer::Quit => break,
+        }
+    }
+
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+
+    fn service(name: &str) -> UnmappedService {
+        UnmappedService {
+            service: name.to_string(),
+            tier: ShippingMethod::Error,
+            rows: 1,
+            label_spend: Money::ZERO,
+            sample_zips: vec![],
+            sample_weights: vec![],
+            suggestion: None,
+        }
+    }
+
+    #[test]
+    fn test_accept_override_skip_and_quit() {
+        let services = vec![
+            service("FedEx Ground Economy [RESA]"),
+            service("Carrier Pigeon"),
+            service("Drone"),
+            service("UPS Next Day Air"),
+            service("Never Asked"),
+        ];
+        let answers = "\nbogus\nunknown\ns\nq\n";
+        let mut rules = vec![];
+        let saved = classify_interactively(&services, answers.as_bytes(), vec![], |rule| {
+            rules.push(rule);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(saved, 2);
+        assert_eq!(rules[0].pattern, "fedex ground economy");
+        assert_eq!(rules[0].tier, ShippingMethod::Ground);
+        assert_eq!(rules[1].pattern, "carrier pigeon");
+        assert_eq!(rules[1].tier, ShippingMethod::Unknown);
+    }
+
+    #[test]
+    fn test_no_proposal_needs_an_answer() {
+        let services = vec![service("Carrier Pigeon")];
+        let mut rules = vec![];
+        let saved = classify_interactively(&services, "\n\nground\n".as_bytes(), vec![], |rule| {
+            rules.push(rule);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(saved, 1);
+        assert_eq!(rules[0].tier, ShippingMethod::Ground);
+    }
+}
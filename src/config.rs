@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+use crate::quarantine::QuarantineConfig;
+use crate::regions::RegionConfig;
+use crate::suggest::SuggestionConfig;
+use crate::taxonomy::FlagConfig;
+use crate::transit::TransitConfig;
+use crate::zones::ZoneConfig;
+
+// RUN CONFIGURATION
+//
+// Settings that change how rows are counted. Every section is optional and
+// falls back to its default, so a missing config file means "defaults".
+
+pub const DEFAULT_CONFIG_PATH: &str = "config.json";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    pub quarantine: QuarantineConfig,
+    pub suggestions: SuggestionConfig,
+    pub transit: TransitConfig,
+    pub regions: RegionConfig,
+    pub zones: ZoneConfig,
+    pub flags: FlagConfig,
+}
+
+/// Loads the config at `path`, or the defaults if there is none.
+pub fn load_config(path: &str) -> Result<Config, Box<dyn Error>> {
+    match crate::read_optional(path, DEFAULT_CONFIG_PATH)? {
+        Some(contents) => Ok(serde_json::from_str(&contents)?),
+        None => Ok(Config::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quarantine::QuarantinePolicy;
+    use crate::ShippingMethod;
+
+    #[test]
+    fn test_partial_config_keeps_defaults() {
+        let config: Config = serde_json::from_str(
+            r#"{"quarantine": {"unknown": {"policy": "reclassify", "to": "Ground"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.quarantine.error, QuarantinePolicy::Exclude);
+        assert_eq!(
+            config.quarantine.unknown,
+            QuarantinePolicy::Reclassify {
+                to: ShippingMethod::Ground
+            }
+        );
+        assert_eq!(
+            serde_json::from_str::<Config>("{}").unwrap(),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn test_flag_exclusions() {
+        let jewelry = [String::from("jewelry")];
+        assert!(Config::default().flags.excludes(&jewelry));
+        assert!(!Config::default().flags.excludes(&[]));
+        let config: Config = serde_json::from_str(r#"{"flags": {"exclude": []}}"#).unwrap();
+        assert!(!config.flags.excludes(&jewelry));
+    }
+}
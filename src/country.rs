@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+// COUNTRIES
+//
+// Recipient countries are normalised to ISO 3166-1 alpha-2 codes. The table
+// in src/countries.json accepts the alpha-2 and alpha-3 codes, the English
+// name and a few common aliases ("UK", "USA", "Deutschland"), ignoring case.
+// The "groups" section puts each country in a group (Canada, Mexico, EU, UK,
+// APAC, ...) for breaking down international averages.
+
+#[derive(Deserialize, Debug)]
+struct CountryRecord {
+    alpha2: String,
+    alpha3: String,
+    name: String,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+impl CountryRecord {
+    fn matches(&self, value: &str) -> bool {
+        value.eq_ignore_ascii_case(&self.alpha2)
+            || value.eq_ignore_ascii_case(&self.alpha3)
+            || value.eq_ignore_ascii_case(&self.name)
+            || self
+                .aliases
+                .iter()
+                .any(|alias| value.eq_ignore_ascii_case(alias))
+    }
+}
+
+#[derive(Deserialize)]
+struct CountryTable {
+    countries: Vec<CountryRecord>,
+    /// Group name to the alpha-2 codes in it.
+    groups: BTreeMap<String, Vec<String>>,
+}
+
+fn table() -> &'static CountryTable {
+    static TABLE: OnceLock<CountryTable> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        serde_json::from_str(include_str!("countries.json"))
+            .expect("built-in country table is valid")
+    })
+}
+
+fn countries() -> &'static [CountryRecord] {
+    &table().countries
+}
+
+/// An ISO 3166-1 alpha-2 country code.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct Country(String);
+
+impl Country {
+    pub fn us() -> Country {
+        Country(String::from("US"))
+    }
+
+    /// Normalises a country cell, or `None` if nothing in the table matches.
+    pub fn parse(value: &str) -> Option<Country> {
+        let value = value.split_whitespace().collect::<Vec<&str>>().join(" ");
+        countries()
+            .iter()
+            .find(|record| record.matches(&value))
+            .map(|record| Country(record.alpha2.clone()))
+    }
+
+    pub fn code(&self) -> &str {
+        &self.0
+    }
+
+    fn record(&self) -> Option<&'static CountryRecord> {
+        countries().iter().find(|record| record.alpha2 == self.0)
+    }
+
+    pub fn name(&self) -> String {
+        self.record()
+            .map(|record| record.name.clone())
+            .unwrap_or_else(|| self.0.clone())
+    }
+
+    /// The continent or carrier-zone group, e.g. "EU" or "APAC".
+    pub fn group(&self) -> String {
+        table()
+            .groups
+            .iter()
+            .find(|(_, codes)| codes.contains(&self.0))
+            .map(|(group, _)| group.clone())
+            .unwrap_or_else(|| String::from("Other"))
+    }
+
+    /// Whether addresses here use US zip codes: the US and its territories.
+    pub fn uses_us_zips(&self) -> bool {
+        matches!(self.code(), "US" | "PR" | "GU" | "VI" | "AS" | "MP")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_names_and_aliases_normalise() {
+        let germany = Some(Country(String::from("DE")));
+        assert_eq!(Country::parse("DE"), germany);
+        assert_eq!(Country::parse("deu"), germany);
+        assert_eq!(Country::parse(" Germany "), germany);
+        assert_eq!(Country::parse("DEUTSCHLAND"), germany);
+        assert_eq!(Country::parse("United  States"), Some(Country::us()));
+        assert_eq!(Country::parse("U.S.A."), Some(Country::us()));
+        assert_eq!(Country::parse("uk").unwrap().code(), "GB");
+        assert_eq!(Country::parse("Atlantis"), None);
+        assert_eq!(Country::parse(""), None);
+    }
+
+    #[test]
+    fn test_only_the_us_and_territories_use_us_zips() {
+        assert!(Country::us().uses_us_zips());
+        assert!(Country::parse("Puerto Rico").unwrap().uses_us_zips());
+        assert!(Country::parse("Guam").unwrap().uses_us_zips());
+        assert!(!Country::parse("FR").unwrap().uses_us_zips());
+    }
+
+    #[test]
+    fn test_groups() {
+        let group = |value: &str| Country::parse(value).unwrap().group();
+        assert_eq!(group("Canada"), "Canada");
+        assert_eq!(group("MEX"), "Mexico");
+        assert_eq!(group("France"), "EU");
+        assert_eq!(group("UK"), "UK");
+        assert_eq!(group("Switzerland"), "Europe");
+        assert_eq!(group("JP"), "APAC");
+        assert_eq!(group("Brazil"), "Latin America");
+        assert_eq!(group("PR"), "United States");
+        assert_eq!(Country::parse("de").unwrap().name(), "Germany");
+    }
+
+    #[test]
+    fn test_every_country_is_in_one_group() {
+        for record in countries() {
+            let groups = table()
+                .groups
+                .values()
+                .filter(|codes| codes.contains(&record.alpha2))
+                .count();
+            assert_eq!(groups, 1, "{} is in {} groups", record.alpha2, groups);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use serde::Serialize;
+
+use crate::profiles::Field;
+use crate::resolve::Resolution;
+use crate::suggest::Suggestion;
+use crate::{OrderFromCSVInput, ShippingMethod};
+
+// ROW ISSUES
+//
+// Every rejected or suspicious input row is recorded with the line it came
+// from, the field at fault, a machine-readable reason and whether the row was
+// still counted in the averages.
+
+#[derive(Serialize, Debug, PartialEq, Clone, Copy, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    UnparseableWeight,
+    ZeroWeight,
+    UnparseableMoney,
+    UnknownService,
+    AmbiguousService,
+    MissingZip,
+    NonUsZip,
+    CarrierMismatch,
+    UnknownCountry,
+}
+
+impl Reason {
+    pub fn name(&self) -> String {
+        match self {
+            Reason::UnparseableWeight => String::from("Weight is not a number"),
+            Reason::ZeroWeight => String::from("Weight is zero"),
+            Reason::UnparseableMoney => String::from("Amount is not a number"),
+            Reason::UnknownService => String::from("Carrier service is not mapped"),
+            Reason::AmbiguousService => String::from("Carrier service has no speed tier"),
+            Reason::MissingZip => String::from("Zip is empty"),
+            Reason::NonUsZip => String::from("Zip is not a US zip"),
+            Reason::UnknownCountry => String::from("Country is not recognised"),
+            Reason::CarrierMismatch => {
+                String::from("Tracking number is from a different carrier than the service")
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Copy, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Disposition {
+    Excluded,
+    Included,
+}
+
+/// A problem with one field of a row, before we know where the row came from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Issue {
+    pub field: Field,
+    pub reason: Reason,
+    pub value: String,
+}
+
+impl Issue {
+    pub fn new(field: Field, reason: Reason, value: &str) -> Issue {
+        Issue {
+            field,
+            reason,
+            value: value.to_string(),
+        }
+    }
+
+    pub fn at(self, line: u64, disposition: Disposition, row: &OrderFromCSVInput) -> RowIssue {
+        RowIssue {
+            line,
+            field: self.field,
+            value: self.value,
+            reason: self.reason,
+            message: self.reason.name(),
+            disposition,
+            reclassified_as: None,
+            suggestion: None,
+            resolution: None,
+            row: row.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RowIssue {
+    pub line: u64,
+    pub field: Field,
+    pub value: String,
+    pub reason: Reason,
+    pub message: String,
+    pub disposition: Disposition,
+    /// Set when a quarantine policy counted the row under another tier.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reclassified_as: Option<ShippingMethod>,
+    /// The most likely tier for an unknown service.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<Suggestion>,
+    /// The tier inferred for an ambiguous service and the evidence for it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<Resolution>,
+    pub row: OrderFromCSVInput,
+}
+
+/// Number of rows excluded from the averages, counting each row once even
+/// when it has several issues.
+pub fn excluded_row_count(issues: &[RowIssue]) -> usize {
+    let mut lines: Vec<u64> = issues
+        .iter()
+        .filter(|issue| issue.disposition == Disposition::Excluded)
+        .map(|issue| issue.line)
+        .collect();
+    lines.dedup();
+    lines.len()
+}
@@ -1,10 +1,54 @@
-use itertools::Itertools;
-use serde::{Deserialize, Deserializer, Serialize};
-use serde_json;
-use std::collections::HashMap;
-use std::fs::OpenOptions;
+mod aggregate;
+mod classification;
+mod classify;
+mod config;
+mod country;
+mod issues;
+mod money;
+mod normalize;
+mod profiles;
+mod quarantine;
+mod regions;
+mod resolve;
+mod suggest;
+mod taxonomy;
+mod tracking;
+mod transit;
+mod unmapped;
+mod zip_prefixes;
+mod zones;
+
+use aggregate::{Area, AverageOutput, Averages, Dimension};
+use classification::{RulesFile, ServiceRules};
+use country::Country;
+use issues::{Disposition, Issue, Reason, RowIssue};
+use money::Money;
+use normalize::ServiceName;
+use profiles::Field;
+use quarantine::{QuarantinePolicy, QuarantineSummary};
+use regions::RegionMap;
+use resolve::TierResolver;
+use serde::{Deserialize, Serialize};
 use std::io::Write;
-use std::{error::Error, fmt, fs, io, process};
+use std::path::Path;
+use std::{env, error::Error, fmt, fs, io};
+use suggest::Suggester;
+use taxonomy::Level;
+use transit::{DeliveryWindow, TransitOutput, TransitReport};
+use unmapped::{UnmappedReport, UnmappedService};
+use zip_prefixes::{ZipPrefixFile, ZipPrefixTable};
+use zones::{ZoneCalculator, ZoneChart, ZoneSource};
+
+/// Reads the file at `path`. Every input file but the csv is optional at its
+/// default path, so a missing file there is `None`; anywhere else it's an
+/// error.
+fn read_optional(path: &str, default_path: &str) -> Result<Option<String>, Box<dyn Error>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound && path == default_path => Ok(None),
+        Err(err) => Err(Box::new(err)),
+    }
+}
 
 // RATE DATA
 // https://www.pirateship.com/usps/zone-map
@@ -16,95 +60,29 @@ fn zip_as_u32(zip_string: &str) -> Result<u32, Box<dyn Error>> {
         }));
     }
 
-    let first_part = zip_string.split('-').next().unwrap_or("");
+    let mut first_part = zip_string.split('-').next().unwrap_or("");
+    // ZIP+4 written without the hyphen, as some 3PL exports do.
+    if first_part.len() == 9 && first_part.bytes().all(|b| b.is_ascii_digit()) {
+        first_part = &first_part[..5];
+    }
 
-    return first_part
+    first_part
         .parse::<u32>()
-        .map_err(|e| Box::new(e) as Box<dyn Error>);
+        .map_err(|e| Box::new(e) as Box<dyn Error>)
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Eq, Hash, PartialOrd, Ord)]
 enum ShippingMethod {
     Economy,
     Ground,
     Express,
     Expedited,
     Priority,
-    Special,
     Unknown,
     Error,
 }
 
 impl ShippingMethod {
-    fn from_str(str: &str) -> ShippingMethod {
-        match str {
-            // economy
-            "UPS Worldwide Saver (Duties Not Paid)" => ShippingMethod::Economy,
-            "UPS SurePost" => ShippingMethod::Economy,
-            "UPS SUREPOST" => ShippingMethod::Economy,
-            "DHL International (Duties Not Paid)" => ShippingMethod::Economy,
-            "DHL International [Route Protection Highly Recommended Not Responsible For Lost Shipment]" => ShippingMethod::Economy,
-            "USPS First-Class Mail [Order Protection Highly Recommended Not Responsible For Lost Shipment]" => ShippingMethod::Economy,
-            "USPS First-Class Mail" => ShippingMethod::Economy,
-            "DHL eCommerce Ground" => ShippingMethod::Economy,
-            "USPS Parcel Post" => ShippingMethod::Economy,
-
-            // ground
-
-
-            "UPS Ground [RESA]" => ShippingMethod::Ground,
-            "FedEx Home Delivery" => ShippingMethod::Ground,
-            "FedEx Ground" => ShippingMethod::Ground,
-            "Upgrade to (3-5 Day) DHL Expedited" => ShippingMethod::Ground,
-
-            // expedited
-            "UPS Next Day Air Saver" => ShippingMethod::Expedited,
-            "FedEx Standard Overnight [RESA]" => ShippingMethod::Expedited,
-            "FedEx Priority Overnight" => ShippingMethod::Expedited,
-            "FedEx Standard Overnight (Envelope)" => ShippingMethod::Expedited,
-            "USPS Express Mail" => ShippingMethod::Expedited,
-            "FedEx Standard Overnight" => ShippingMethod::Expedited,
-
-
-            // express
-
-            "UPS Worldwide Express (Duties Not Paid)" => ShippingMethod::Express,
-            "FedEx One Rate (Pak) 2-Day [RESA]" => ShippingMethod::Express,
-            "FedEx One Rate (Envelope) 2-Day [RESA]" => ShippingMethod::Express,
-            "UPS 2nd Day Air" => ShippingMethod::Express,
-            "FedEx 2nd Day [RESA]" => ShippingMethod::Express,
-            "USPS Priority Mail" => ShippingMethod::Express,
-
-
-
-
-            // priority
-
-            "FedEx Intl Priority (Envelope) (Duties Not Paid)" => ShippingMethod::Priority,
-            "USPS Priority Mail International (Duties Not Paid)" => ShippingMethod::Priority,
-            "FedEx Intl Connect Plus (Duties Not Paid) [RESA]" => ShippingMethod::Priority,
-            "FedEx 2nd Day" => ShippingMethod::Priority,
-            "FedEx International Priority (Duties Not Paid)" => ShippingMethod::Priority,
-            "fedex overnight" => ShippingMethod::Priority,
-            "FEDEX overnight" => ShippingMethod::Priority,
-
-            // special cases - jewelry or transfer
-            "FedEx One Rate (Pak) 2-Day [RESA JEWELRY]" => ShippingMethod::Special,
-            "Misc Transfer Carrier" => ShippingMethod::Special,
-
-            // these ones are sus - found international stuff etc. maybe ground?
-            "USPS" => ShippingMethod::Unknown,
-            "fedex" => ShippingMethod::Unknown,
-            "FEDEX" => ShippingMethod::Unknown,
-            "ups" => ShippingMethod::Unknown,
-            "UPS" => ShippingMethod::Unknown,
-            "usps" => ShippingMethod::Unknown,
-            "FEDEx" => ShippingMethod::Unknown,
-            "USPS First-Class Mail [Route Protection Highly Recommended Not Responsible For Lost Shipment]" => ShippingMethod::Unknown,
-
-            _ => ShippingMethod::Error,
-        }
-    }
     fn name(&self) -> String {
         match self {
             ShippingMethod::Economy => String::from("Economy"),
@@ -112,13 +90,146 @@ impl ShippingMethod {
             ShippingMethod::Express => String::from("Express"),
             ShippingMethod::Expedited => String::from("Expedited"),
             ShippingMethod::Priority => String::from("Priority"),
-            ShippingMethod::Special => String::from("Special"),
             ShippingMethod::Unknown => String::from("Unknown"),
             ShippingMethod::Error => String::from("Error"),
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Eq, Hash, PartialOrd, Ord)]
+enum Carrier {
+    #[serde(rename = "UPS")]
+    Ups,
+    FedEx,
+    #[serde(rename = "USPS")]
+    Usps,
+    #[serde(rename = "DHL")]
+    Dhl,
+    Other,
+}
+
+impl Carrier {
+    /// Detects the carrier from a canonical service key by looking for the
+    /// carrier's name as a word, e.g. "ups surepost" or "usps priority mail".
+    fn from_service_key(key: &str) -> Carrier {
+        key.split(|c: char| !c.is_ascii_alphanumeric())
+            .find_map(|word| match word {
+                "ups" => Some(Carrier::Ups),
+                "fedex" => Some(Carrier::FedEx),
+                "usps" => Some(Carrier::Usps),
+                "dhl" => Some(Carrier::Dhl),
+                _ => None,
+            })
+            .unwrap_or(Carrier::Other)
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Carrier::Ups => String::from("UPS"),
+            Carrier::FedEx => String::from("FedEx"),
+            Carrier::Usps => String::from("USPS"),
+            Carrier::Dhl => String::from("DHL"),
+            Carrier::Other => String::from("Other"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Eq, Hash, PartialOrd, Ord)]
+enum Scope {
+    Domestic,
+    International,
+}
+
+impl Scope {
+    /// International services say so: "intl", "international", "worldwide".
+    fn from_service_key(key: &str) -> Scope {
+        let international = key
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .any(|word| matches!(word, "intl" | "international" | "worldwide"));
+        if international {
+            Scope::International
+        } else {
+            Scope::Domestic
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Scope::Domestic => String::from("Domestic"),
+            Scope::International => String::from("International"),
+        }
+    }
+}
+
+/// Who pays import duties: the recipient on delivery (DDU) or the shipper
+/// up front (DDP).
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Eq, Hash, PartialOrd, Ord)]
+enum DutiesTerm {
+    #[serde(rename = "DDU")]
+    Ddu,
+    #[serde(rename = "DDP")]
+    Ddp,
+    Unknown,
+}
+
+impl DutiesTerm {
+    fn from_service_key(key: &str) -> DutiesTerm {
+        let words: Vec<&str> = key
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        if key.contains("duties not paid") || words.contains(&"ddu") {
+            DutiesTerm::Ddu
+        } else if key.contains("duties paid") || words.contains(&"ddp") {
+            DutiesTerm::Ddp
+        } else {
+            DutiesTerm::Unknown
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            DutiesTerm::Ddu => String::from("DDU"),
+            DutiesTerm::Ddp => String::from("DDP"),
+            DutiesTerm::Unknown => String::from("Unknown"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+enum PackageType {
+    Envelope,
+    Pak,
+    Box,
+    Unknown,
+}
+
+impl PackageType {
+    /// Flat-rate and One Rate services name their packaging, e.g.
+    /// "fedex one rate (pak) 2-day" or "fedex standard overnight (envelope)".
+    fn from_service_key(key: &str) -> PackageType {
+        key.split(|c: char| !c.is_ascii_alphanumeric())
+            .find_map(|word| match word {
+                "envelope" | "envelopes" => Some(PackageType::Envelope),
+                "pak" | "paks" => Some(PackageType::Pak),
+                "box" | "boxes" => Some(PackageType::Box),
+                _ => None,
+            })
+            .unwrap_or(PackageType::Unknown)
+    }
+
+    fn name(&self) -> String {
+        match self {
+            PackageType::Envelope => String::from("Envelope"),
+            PackageType::Pak => String::from("Pak"),
+            PackageType::Box => String::from("Box"),
+            PackageType::Unknown => String::from("Unknown"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Eq, Hash, PartialOrd, Ord)]
 enum Province {
     AK,
     AL,
@@ -172,90 +283,247 @@ enum Province {
     WI,
     WV,
     WY,
+    // Territories
+    AS,
+    GU,
+    MP,
+    VI,
+    // Military "states": the Americas, Europe and the Pacific
+    AA,
+    AE,
+    AP,
 }
 
 impl Province {
-    fn from_zip_code(zip_code: u32) -> Result<Province, Box<dyn Error>> {
-        match zip_code {
-            20042..=20042 => Ok(Province::VA),
-            20331..=20331 => Ok(Province::MD),
-            99501..=99950 => Ok(Province::AK),
-            35004..=36925 => Ok(Province::AL),
-            71601..=72959 => Ok(Province::AR),
-            75502..=75502 => Ok(Province::AR),
-            85001..=86556 => Ok(Province::AZ),
-            90001..=96162 => Ok(Province::CA),
-            80001..=81658 => Ok(Province::CO),
-            6001..=6389 => Ok(Province::CT),
-            6401..=6928 => Ok(Province::CT),
-            20001..=20039 => Ok(Province::DC),
-            20042..=20599 => Ok(Province::DC),
-            20799..=20799 => Ok(Province::DC),
-            19701..=19980 => Ok(Province::DE),
-            32004..=34997 => Ok(Province::FL),
-            30001..=31999 => Ok(Province::GA),
-            39901..=39901 => Ok(Province::GA),
-            96701..=96898 => Ok(Province::HI),
-            50001..=52809 => Ok(Province::IA),
-            68119..=68120 => Ok(Province::IA),
-            83201..=83876 => Ok(Province::ID),
-            60001..=62999 => Ok(Province::IL),
-            46001..=47997 => Ok(Province::IN),
-            66002..=67954 => Ok(Province::KS),
-            40003..=42788 => Ok(Province::KY),
-            70001..=71232 => Ok(Province::LA),
-            71234..=71497 => Ok(Province::LA),
-            1001..=2791 => Ok(Province::MA),
-            5501..=5544 => Ok(Province::MA),
-            20335..=20797 => Ok(Province::MD),
-            20812..=21930 => Ok(Province::MD),
-            3901..=4992 => Ok(Province::ME),
-            48001..=49971 => Ok(Province::MI),
-            55001..=56763 => Ok(Province::MN),
-            63001..=65899 => Ok(Province::MO),
-            38601..=39776 => Ok(Province::MS),
-            71233..=71233 => Ok(Province::MS),
-            59001..=59937 => Ok(Province::MT),
-            27006..=28909 => Ok(Province::NC),
-            58001..=58856 => Ok(Province::ND),
-            68001..=68118 => Ok(Province::NE),
-            68122..=69367 => Ok(Province::NE),
-            3031..=3897 => Ok(Province::NH),
-            7001..=8989 => Ok(Province::NJ),
-            87001..=88441 => Ok(Province::NM),
-            88901..=89883 => Ok(Province::NV),
-            6390..=6390 => Ok(Province::NY),
-            10001..=14975 => Ok(Province::NY),
-            43001..=45999 => Ok(Province::OH),
-            73001..=73199 => Ok(Province::OK),
-            73401..=74966 => Ok(Province::OK),
-            97001..=97920 => Ok(Province::OR),
-            15001..=19640 => Ok(Province::PA),
-            00600..=00799 => Ok(Province::PR),
-            00900..=00999 => Ok(Province::PR),
-            2801..=2940 => Ok(Province::RI),
-            29001..=29948 => Ok(Province::SC),
-            57001..=57799 => Ok(Province::SD),
-            37010..=38589 => Ok(Province::TN),
-            73301..=73301 => Ok(Province::TX),
-            75001..=75501 => Ok(Province::TX),
-            75503..=79999 => Ok(Province::TX),
-            88510..=88589 => Ok(Province::TX),
-            84001..=84784 => Ok(Province::UT),
-            20040..=20041 => Ok(Province::VA),
-            20040..=20167 => Ok(Province::VA),
-            22001..=24658 => Ok(Province::VA),
-            5001..=5495 => Ok(Province::VT),
-            5601..=5907 => Ok(Province::VT),
-            98001..=99403 => Ok(Province::WA),
-            53001..=54990 => Ok(Province::WI),
-            24701..=26886 => Ok(Province::WV),
-            82001..=83128 => Ok(Province::WY),
-            _ => Err(Box::new(UnexpectedError {
-                message: "Invalid Zip Range.".to_string(),
-            })),
+    /// The state of a US or US territory address, or `None` for addresses
+    /// abroad, in unrecognised countries and at zips that aren't in the table.
+    fn from_destination(
+        country: Option<&Country>,
+        zip_string: &str,
+        zips: &ZipPrefixTable,
+    ) -> Option<Province> {
+        if !country.is_some_and(Country::uses_us_zips) {
+            return None;
+        }
+        zip_as_u32(zip_string)
+            .ok()
+            .and_then(|zip| Province::from_zip_code(zip, zips).ok())
+    }
+
+    /// Whether averages for all regions count this state, whichever region
+    /// scheme is in use. Alaska, Hawaii, the territories and military mail
+    /// are left out.
+    fn in_all_regions(&self) -> bool {
+        use Province::*;
+        !matches!(self, AK | HI | AS | GU | MP | VI | AA | AE | AP)
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Province::AK => String::from("Alaska"),
+            Province::AL => String::from("Alabama"),
+            Province::AR => String::from("Arkansas"),
+            Province::AZ => String::from("Arizona"),
+            Province::CA => String::from("California"),
+            Province::CO => String::from("Colorado"),
+            Province::CT => String::from("Connecticut"),
+            Province::DC => String::from("District of Columbia"),
+            Province::DE => String::from("Delaware"),
+            Province::FL => String::from("Florida"),
+            Province::GA => String::from("Georgia"),
+            Province::HI => String::from("Hawaii"),
+            Province::IA => String::from("Iowa"),
+            Province::ID => String::from("Idaho"),
+            Province::IL => String::from("Illinois"),
+            Province::IN => String::from("Indiana"),
+            Province::KS => String::from("Kansas"),
+            Province::KY => String::from("Kentucky"),
+            Province::LA => String::from("Louisiana"),
+            Province::MA => String::from("Massachusetts"),
+            Province::MD => String::from("Maryland"),
+            Province::ME => String::from("Maine"),
+            Province::MI => String::from("Michigan"),
+            Province::MN => String::from("Minnesota"),
+            Province::MO => String::from("Missouri"),
+            Province::MS => String::from("Mississippi"),
+            Province::MT => String::from("Montana"),
+            Province::NC => String::from("North Carolina"),
+            Province::ND => String::from("North Dakota"),
+            Province::NE => String::from("Nebraska"),
+            Province::NH => String::from("New Hampshire"),
+            Province::NJ => String::from("New Jersey"),
+            Province::NM => String::from("New Mexico"),
+            Province::NV => String::from("Nevada"),
+            Province::NY => String::from("New York"),
+            Province::OH => String::from("Ohio"),
+            Province::OK => String::from("Oklahoma"),
+            Province::OR => String::from("Oregon"),
+            Province::PA => String::from("Pennsylvania"),
+            Province::PR => String::from("Puerto Rico"),
+            Province::RI => String::from("Rhode Island"),
+            Province::SC => String::from("South Carolina"),
+            Province::SD => String::from("South Dakota"),
+            Province::TN => String::from("Tennessee"),
+            Province::TX => String::from("Texas"),
+            Province::UT => String::from("Utah"),
+            Province::VA => String::from("Virginia"),
+            Province::VT => String::from("Vermont"),
+            Province::WA => String::from("Washington"),
+            Province::WI => String::from("Wisconsin"),
+            Province::WV => String::from("West Virginia"),
+            Province::WY => String::from("Wyoming"),
+            Province::AS => String::from("American Samoa"),
+            Province::GU => String::from("Guam"),
+            Province::MP => String::from("Northern Mariana Islands"),
+            Province::VI => String::from("U.S. Virgin Islands"),
+            Province::AA => String::from("Armed Forces Americas"),
+            Province::AE => String::from("Armed Forces Europe"),
+            Province::AP => String::from("Armed Forces Pacific"),
         }
     }
+
+    /// The two digit FIPS code. Military "states" have none.
+    fn fips(&self) -> Option<String> {
+        match self {
+            Province::AK => Some(String::from("02")),
+            Province::AL => Some(String::from("01")),
+            Province::AR => Some(String::from("05")),
+            Province::AZ => Some(String::from("04")),
+            Province::CA => Some(String::from("06")),
+            Province::CO => Some(String::from("08")),
+            Province::CT => Some(String::from("09")),
+            Province::DC => Some(String::from("11")),
+            Province::DE => Some(String::from("10")),
+            Province::FL => Some(String::from("12")),
+            Province::GA => Some(String::from("13")),
+            Province::HI => Some(String::from("15")),
+            Province::IA => Some(String::from("19")),
+            Province::ID => Some(String::from("16")),
+            Province::IL => Some(String::from("17")),
+            Province::IN => Some(String::from("18")),
+            Province::KS => Some(String::from("20")),
+            Province::KY => Some(String::from("21")),
+            Province::LA => Some(String::from("22")),
+            Province::MA => Some(String::from("25")),
+            Province::MD => Some(String::from("24")),
+            Province::ME => Some(String::from("23")),
+            Province::MI => Some(String::from("26")),
+            Province::MN => Some(String::from("27")),
+            Province::MO => Some(String::from("29")),
+            Province::MS => Some(String::from("28")),
+            Province::MT => Some(String::from("30")),
+            Province::NC => Some(String::from("37")),
+            Province::ND => Some(String::from("38")),
+            Province::NE => Some(String::from("31")),
+            Province::NH => Some(String::from("33")),
+            Province::NJ => Some(String::from("34")),
+            Province::NM => Some(String::from("35")),
+            Province::NV => Some(String::from("32")),
+            Province::NY => Some(String::from("36")),
+            Province::OH => Some(String::from("39")),
+            Province::OK => Some(String::from("40")),
+            Province::OR => Some(String::from("41")),
+            Province::PA => Some(String::from("42")),
+            Province::PR => Some(String::from("72")),
+            Province::RI => Some(String::from("44")),
+            Province::SC => Some(String::from("45")),
+            Province::SD => Some(String::from("46")),
+            Province::TN => Some(String::from("47")),
+            Province::TX => Some(String::from("48")),
+            Province::UT => Some(String::from("49")),
+            Province::VA => Some(String::from("51")),
+            Province::VT => Some(String::from("50")),
+            Province::WA => Some(String::from("53")),
+            Province::WI => Some(String::from("55")),
+            Province::WV => Some(String::from("54")),
+            Province::WY => Some(String::from("56")),
+            Province::AS => Some(String::from("60")),
+            Province::GU => Some(String::from("66")),
+            Province::MP => Some(String::from("69")),
+            Province::VI => Some(String::from("78")),
+            Province::AA | Province::AE | Province::AP => None,
+        }
+    }
+
+    fn from_code(code: &str) -> Option<Province> {
+        match code {
+            "AK" => Some(Province::AK),
+            "AL" => Some(Province::AL),
+            "AR" => Some(Province::AR),
+            "AZ" => Some(Province::AZ),
+            "CA" => Some(Province::CA),
+            "CO" => Some(Province::CO),
+            "CT" => Some(Province::CT),
+            "DC" => Some(Province::DC),
+            "DE" => Some(Province::DE),
+            "FL" => Some(Province::FL),
+            "GA" => Some(Province::GA),
+            "HI" => Some(Province::HI),
+            "IA" => Some(Province::IA),
+            "ID" => Some(Province::ID),
+            "IL" => Some(Province::IL),
+            "IN" => Some(Province::IN),
+            "KS" => Some(Province::KS),
+            "KY" => Some(Province::KY),
+            "LA" => Some(Province::LA),
+            "MA" => Some(Province::MA),
+            "MD" => Some(Province::MD),
+            "ME" => Some(Province::ME),
+            "MI" => Some(Province::MI),
+            "MN" => Some(Province::MN),
+            "MO" => Some(Province::MO),
+            "MS" => Some(Province::MS),
+            "MT" => Some(Province::MT),
+            "NC" => Some(Province::NC),
+            "ND" => Some(Province::ND),
+            "NE" => Some(Province::NE),
+            "NH" => Some(Province::NH),
+            "NJ" => Some(Province::NJ),
+            "NM" => Some(Province::NM),
+            "NV" => Some(Province::NV),
+            "NY" => Some(Province::NY),
+            "OH" => Some(Province::OH),
+            "OK" => Some(Province::OK),
+            "OR" => Some(Province::OR),
+            "PA" => Some(Province::PA),
+            "PR" => Some(Province::PR),
+            "RI" => Some(Province::RI),
+            "SC" => Some(Province::SC),
+            "SD" => Some(Province::SD),
+            "TN" => Some(Province::TN),
+            "TX" => Some(Province::TX),
+            "UT" => Some(Province::UT),
+            "VA" => Some(Province::VA),
+            "VT" => Some(Province::VT),
+            "WA" => Some(Province::WA),
+            "WI" => Some(Province::WI),
+            "WV" => Some(Province::WV),
+            "WY" => Some(Province::WY),
+            "AS" => Some(Province::AS),
+            "GU" => Some(Province::GU),
+            "MP" => Some(Province::MP),
+            "VI" => Some(Province::VI),
+            "AA" => Some(Province::AA),
+            "AE" => Some(Province::AE),
+            "AP" => Some(Province::AP),
+            _ => None,
+        }
+    }
+
+    fn from_zip_code(zip_code: u32, zips: &ZipPrefixTable) -> Result<Province, Box<dyn Error>> {
+        if zip_code > 99999 {
+            return Err(Box::new(UnexpectedError {
+                message: "Zip has more than 5 digits.".to_string(),
+            }));
+        }
+        zips.state_for(zip_code)
+            .and_then(Province::from_code)
+            .ok_or_else(|| {
+                Box::new(UnexpectedError {
+                    message: "Invalid Zip Range.".to_string(),
+                }) as Box<dyn Error>
+            })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Eq, Hash)]
@@ -272,36 +540,32 @@ enum Region {
     PuertoRico,
     Hawaii,
     Alaska,
+    Territories,
+    Military,
     International,
+    /// A region of a configured scheme that isn't one of the above.
+    #[serde(untagged)]
+    Named(String),
 }
 
 impl Region {
-    fn from_string_zip(zip_string: &str) -> Region {
-        // 20_000 is higher than any us zip ID
-        let zip = zip_as_u32(zip_string).unwrap_or(20_000);
-        let province = Province::from_zip_code(zip);
-        if let Ok(province_value) = province {
-            return Region::from_province(province_value);
-        } else {
-            Region::International
-        }
+    fn from_string_zip(zip_string: &str, geography: &Geography) -> Region {
+        Region::try_from_string_zip(zip_string, geography).unwrap_or(Region::International)
     }
 
-    fn from_province(province: Province) -> Region {
-        use Province::*;
-        match province {
-            ME | NH | VT | MA | RI | CT | NY | NJ | PA => Region::Northeast,
-            DC | DE | MD | VA | WV | NC => Region::MidAtlantic,
-            KY | LA | AR | SC | GA | FL | AL | MS | TN => Region::Southeast,
-            OH | MI | IN | IL | WI | MN | IA | MO => Region::Midwest,
-            ND | SD | NE | KS | OK => Region::GreatPlains,
-            TX | NM | AZ => Region::Southwest,
-            CO | WY | MT | ID | UT | NV => Region::Mountain,
-            CA | OR | WA => Region::WestCoast,
-            PR => Region::PuertoRico,
-            HI => Region::Hawaii,
-            AK => Region::Alaska,
-        }
+    fn try_from_string_zip(
+        zip_string: &str,
+        geography: &Geography,
+    ) -> Result<Region, Box<dyn Error>> {
+        let zip = zip_as_u32(zip_string)?;
+        let province = Province::from_zip_code(zip, &geography.zips)?;
+        Ok(geography.regions.region_for(province))
+    }
+
+    /// The built-in region called `name`, or else a named one.
+    fn from_name(name: &str) -> Region {
+        serde_json::from_value(serde_json::Value::String(name.to_string()))
+            .unwrap_or_else(|_| Region::Named(name.to_string()))
     }
 
     fn name(&self) -> String {
@@ -317,8 +581,62 @@ impl Region {
             Region::PuertoRico => String::from("Puerto Rico"),
             Region::Hawaii => String::from("Hawaii"),
             Region::Alaska => String::from("Alaska"),
+            Region::Territories => String::from("Territories"),
+            Region::Military => String::from("Military"),
             Region::International => String::from("International"),
             Region::All => String::from("All Regions"),
+            Region::Named(name) => name.clone(),
+        }
+    }
+}
+
+/// The tables destinations are looked up in: zip prefixes to states, states
+/// to regions, and zones from the origin. Built once from the options and
+/// config, then handed to everything that places an order.
+struct Geography {
+    zips: ZipPrefixTable,
+    regions: RegionMap,
+    zones: ZoneCalculator,
+}
+
+impl Geography {
+    /// Loads the zip prefix file and zone chart, warning about problems in
+    /// the zip prefixes.
+    fn load(options: &Options, config: &config::Config) -> Result<Geography, Box<dyn Error>> {
+        let file = ZipPrefixFile::load(&options.zip_prefixes_path)?;
+        let problems = file.check();
+        if !problems.is_empty() {
+            eprintln!(
+                "Warning: {} problems in the zip prefix table, see validate-zips.",
+                problems.len()
+            );
+        }
+        let zips = ZipPrefixTable::new(&file)?;
+
+        let origin_zip = if options.origin_zip.is_empty() {
+            &config.zones.origin_zip
+        } else {
+            &options.origin_zip
+        };
+        let chart = ZoneChart::load(&options.zone_chart_path)?;
+        let zones = ZoneCalculator::new(origin_zip, chart.as_ref(), &zips)?;
+
+        Ok(Geography {
+            zips,
+            regions: config.regions.selected()?,
+            zones,
+        })
+    }
+
+    /// The built-in zip prefixes and region scheme, without zones.
+    #[cfg(test)]
+    fn built_in() -> Geography {
+        Geography {
+            zips: ZipPrefixTable::built_in(),
+            regions: regions::RegionConfig::default()
+                .selected()
+                .expect("default region scheme is valid"),
+            zones: ZoneCalculator::NoOrigin,
         }
     }
 }
@@ -334,7 +652,7 @@ enum WeightRange {
 impl WeightRange {
     fn from_str(weight_str: &str) -> WeightRange {
         let weight_result = weight_str.parse::<f32>();
-        return if let Ok(weight_as_f32) = weight_result {
+        if let Ok(weight_as_f32) = weight_result {
             if weight_as_f32 < 2.0 {
                 return WeightRange::Under2Lbs;
             }
@@ -344,7 +662,7 @@ impl WeightRange {
             WeightRange::Over5Lbs
         } else {
             WeightRange::Unknown
-        };
+        }
     }
     fn name(&self) -> String {
         match self {
@@ -356,7 +674,7 @@ impl WeightRange {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 struct OrderFromCSVInput {
     zip: String,
     retail_value: String,
@@ -365,99 +683,267 @@ struct OrderFromCSVInput {
     label_cost: String,
     packaging_cost: String,
     labor_cost: String,
+    tracking_number: String,
+    country: String,
+}
+
+#[cfg(test)]
+impl OrderFromCSVInput {
+    /// A row with the columns tests vary and the rest blank.
+    fn row(
+        zip: &str,
+        shipping_method: &str,
+        ship_weight: &str,
+        label_cost: &str,
+        retail_value: &str,
+    ) -> OrderFromCSVInput {
+        OrderFromCSVInput {
+            zip: zip.to_string(),
+            retail_value: retail_value.to_string(),
+            ship_weight: ship_weight.to_string(),
+            shipping_method: shipping_method.to_string(),
+            label_cost: label_cost.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The order a run builds from the row, with the built-in rules and
+    /// geography.
+    fn to_order(&self) -> Order {
+        Order::new_from_csv_without_packaging_cost(
+            self,
+            &ServiceRules::built_in(),
+            &Geography::built_in(),
+        )
+        .unwrap()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Order {
     ship_weight: WeightRange,
-    retail_value: f32,
-    shipping_cost: f32,
-    shipping_cost_per_pound: f32,
+    retail_value: Money,
+    shipping_cost: Money,
+    shipping_cost_per_pound: f64,
     shipping_method: ShippingMethod,
+    carrier: Carrier,
+    /// The carrier the tracking number belongs to, if it has a valid one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tracking_carrier: Option<Carrier>,
+    /// The tier of the service the tracking number's format points to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tracking_tier: Option<ShippingMethod>,
+    scope: Scope,
+    duties: DutiesTerm,
+    package: PackageType,
+    product: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    flags: Vec<String>,
+    service: ServiceName,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    country: Option<Country>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<Province>,
     region: Region,
+    /// The shipping zone from the origin, if one is configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    zone: Option<u8>,
+    /// Whether the zone is exact, from a chart, or estimated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    zone_source: Option<ZoneSource>,
 }
 
 impl Order {
-    fn new_from_csv(input: &OrderFromCSVInput) -> Option<Order> {
-        let ship_weight = WeightRange::from_str(&input.ship_weight);
-        let ship_weight_f32 = input.ship_weight.parse::<f32>().ok()?;
-        let region = Region::from_string_zip(&input.zip);
-        let retail_value = input.retail_value.parse::<f32>().unwrap_or(0.0);
-        let shipping_method = ShippingMethod::from_str(&input.shipping_method);
-
-        let label_cost = input.label_cost.parse::<f32>().unwrap_or(0.0);
-        let packaging_cost = input.packaging_cost.parse::<f32>().unwrap_or(0.0);
-        let labor_cost = input.labor_cost.parse::<f32>().unwrap_or(0.0);
-        let shipping_cost = labor_cost + label_cost + packaging_cost;
-        let shipping_cost_per_pound = shipping_cost / ship_weight_f32;
-
-        return Some(Order {
-            ship_weight,
-            region,
-            shipping_cost,
-            shipping_cost_per_pound,
-            retail_value,
-            shipping_method,
-        });
+    #[cfg(test)]
+    fn new_from_csv(
+        input: &OrderFromCSVInput,
+        rules: &ServiceRules,
+        geography: &Geography,
+    ) -> Result<Order, Issue> {
+        Order::from_input(input, rules, geography, true)
+    }
+
+    fn new_from_csv_without_packaging_cost(
+        input: &OrderFromCSVInput,
+        rules: &ServiceRules,
+        geography: &Geography,
+    ) -> Result<Order, Issue> {
+        Order::from_input(input, rules, geography, false)
     }
 
-    fn new_from_csv_without_packaging_cost(input: &OrderFromCSVInput) -> Option<Order> {
+    /// Builds an order from a row. The shipping cost is the label cost, plus
+    /// the packaging and labor costs if `with_packaging_cost`.
+    fn from_input(
+        input: &OrderFromCSVInput,
+        rules: &ServiceRules,
+        geography: &Geography,
+        with_packaging_cost: bool,
+    ) -> Result<Order, Issue> {
         let ship_weight = WeightRange::from_str(&input.ship_weight);
-        let ship_weight_f32 = input.ship_weight.parse::<f32>().ok()?;
-        let region = Region::from_string_zip(&input.zip);
-        let retail_value = input.retail_value.parse::<f32>().unwrap_or(0.0);
-        let shipping_method = ShippingMethod::from_str(&input.shipping_method);
+        let ship_weight_f32 = parse_weight(&input.ship_weight)?;
+        let country = parse_country(&input.country);
+        let state = Province::from_destination(country.as_ref(), &input.zip, &geography.zips);
+        let region = state.map_or(Region::International, |state| {
+            geography.regions.region_for(state)
+        });
+        let zone = geography.zones.zone_for(state, &input.zip);
+        let zone_source = zone.and(geography.zones.source());
+        let retail_value = parse_money_field(Field::RetailValue, &input.retail_value)?;
+        let classification = rules.classify(&input.shipping_method);
+        let service = ServiceName::parse(&input.shipping_method);
+        let tracking_carrier = tracking::carrier_for(&input.tracking_number);
+        let tracking_tier = tracking::service_for(&input.tracking_number)
+            .map(|service| rules.tier(service))
+            .filter(|tier| !matches!(tier, ShippingMethod::Unknown | ShippingMethod::Error));
+        let carrier = match (&classification.carrier, &tracking_carrier) {
+            (Carrier::Other, Some(tracked)) => tracked.clone(),
+            _ => classification.carrier.clone(),
+        };
 
-        let label_cost = input.label_cost.parse::<f32>().unwrap_or(0.0);
-        let shipping_cost = label_cost;
-        let shipping_cost_per_pound = shipping_cost / ship_weight_f32;
+        let label_cost = parse_money_field(Field::LabelCost, &input.label_cost)?;
+        let shipping_cost = if with_packaging_cost {
+            let packaging_cost = parse_money_field(Field::PackagingCost, &input.packaging_cost)?;
+            let labor_cost = parse_money_field(Field::LaborCost, &input.labor_cost)?;
+            labor_cost + label_cost + packaging_cost
+        } else {
+            label_cost
+        };
+        let shipping_cost_per_pound = shipping_cost.to_f64() / f64::from(ship_weight_f32);
 
-        return Some(Order {
+        Ok(Order {
             ship_weight,
             region,
+            zone,
+            zone_source,
             shipping_cost,
             shipping_cost_per_pound,
             retail_value,
-            shipping_method,
-        });
+            shipping_method: classification.tier,
+            carrier,
+            tracking_carrier,
+            tracking_tier,
+            scope: classification.scope,
+            duties: classification.duties,
+            package: classification.package,
+            product: classification.product,
+            flags: classification.flags,
+            service,
+            country,
+            state,
+        })
     }
-}
 
-// FILE OPS
+    /// Problems with a successfully parsed order that don't stop it being
+    /// counted.
+    fn issues(&self, input: &OrderFromCSVInput) -> Vec<Issue> {
+        let mut issues = vec![];
+        match self.shipping_method {
+            ShippingMethod::Error => issues.push(Issue::new(
+                Field::ShippingMethod,
+                Reason::UnknownService,
+                &input.shipping_method,
+            )),
+            // Flagged services like stock transfers have no speed tier by
+            // design; only bare carrier names are ambiguous.
+            ShippingMethod::Unknown if self.flags.is_empty() => issues.push(Issue::new(
+                Field::ShippingMethod,
+                Reason::AmbiguousService,
+                &input.shipping_method,
+            )),
+            _ => {}
+        }
+        if self.country.is_none() {
+            issues.push(Issue::new(
+                Field::Country,
+                Reason::UnknownCountry,
+                &input.country,
+            ));
+        }
+        // Foreign postal codes, and those in unrecognised countries, aren't
+        // checked.
+        if self.country.as_ref().is_some_and(Country::uses_us_zips) {
+            if input.zip.trim().is_empty() {
+                issues.push(Issue::new(Field::Zip, Reason::MissingZip, &input.zip));
+            } else if self.state.is_none() {
+                issues.push(Issue::new(Field::Zip, Reason::NonUsZip, &input.zip));
+            }
+        }
+        if let Some(tracked) = &self.tracking_carrier {
+            if *tracked != self.carrier {
+                issues.push(Issue::new(
+                    Field::TrackingNumber,
+                    Reason::CarrierMismatch,
+                    &input.tracking_number,
+                ));
+            }
+        }
+        issues
+    }
+}
 
-fn append_line_to_file(file_path: &str, line: String) -> std::io::Result<()> {
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .create(true)
-        .open(file_path)?;
+/// A blank country is taken to be the US, as in exports without the column.
+fn parse_country(value: &str) -> Option<Country> {
+    if value.trim().is_empty() {
+        return Some(Country::us());
+    }
+    Country::parse(value)
+}
 
-    writeln!(file, "{}", line)?;
+fn parse_weight(weight_str: &str) -> Result<f32, Issue> {
+    let weight = weight_str
+        .parse::<f32>()
+        .map_err(|_| Issue::new(Field::ShipWeight, Reason::UnparseableWeight, weight_str))?;
+    if weight == 0.0 {
+        return Err(Issue::new(
+            Field::ShipWeight,
+            Reason::ZeroWeight,
+            weight_str,
+        ));
+    }
+    Ok(weight)
+}
 
-    Ok(())
+fn parse_money_field(field: Field, value: &str) -> Result<Money, Issue> {
+    money::parse_money(value).map_err(|_| Issue::new(field, Reason::UnparseableMoney, value))
 }
 
-fn write_to_output_file(data: &Vec<Order>) -> Result<(), Box<dyn Error>> {
+// FILE OPS
+
+fn write_to_output_file(data: &[Order]) -> Result<(), Box<dyn Error>> {
     let json_string = serde_json::to_string_pretty(&data)?;
     let mut file = fs::File::create("output.json")?;
     file.write_all(json_string.as_bytes())?;
     Ok(())
 }
 
-fn write_to_error_file(data: &Vec<OrderFromCSVInput>) -> Result<(), Box<dyn Error>> {
+fn write_to_error_file(data: &[RowIssue]) -> Result<(), Box<dyn Error>> {
     let json_string = serde_json::to_string_pretty(&data)?;
     let mut file = fs::File::create("errors.json")?;
     file.write_all(json_string.as_bytes())?;
     Ok(())
 }
 
-fn write_avgs_to_output_file(data: &Vec<AverageOutput>) -> Result<(), Box<dyn Error>> {
+fn write_unmapped_services_file(data: &[UnmappedService]) -> Result<(), Box<dyn Error>> {
+    let json_string = serde_json::to_string_pretty(&data)?;
+    let mut file = fs::File::create("unmapped_services.json")?;
+    file.write_all(json_string.as_bytes())?;
+    Ok(())
+}
+
+fn write_avgs_to_output_file(data: &[AverageOutput]) -> Result<(), Box<dyn Error>> {
     let json_string = serde_json::to_string_pretty(&data)?;
     let mut file = fs::File::create("avg_output.json")?;
     file.write_all(json_string.as_bytes())?;
     Ok(())
 }
 
+fn write_transit_to_output_file(data: &[TransitOutput]) -> Result<(), Box<dyn Error>> {
+    let json_string = serde_json::to_string_pretty(&data)?;
+    let mut file = fs::File::create("transit_output.json")?;
+    file.write_all(json_string.as_bytes())?;
+    Ok(())
+}
+
 fn write_to_csv(avgs: &[AverageOutput]) -> Result<(), Box<dyn Error>> {
     let file = fs::File::create("output.csv")?;
     let mut wtr = csv::Writer::from_writer(file);
@@ -485,278 +971,471 @@ impl fmt::Display for UnexpectedError {
 
 impl Error for UnexpectedError {}
 
-// CSV PARSE FUNCTION
+// COMMAND LINE
 
-struct Counter {
-    total_retail_cost: f32,
-    total_item_count: f32,
-    total_shipping_cost: f32,
-    total_shipping_cost_per_pound: f32,
+#[derive(Debug, PartialEq)]
+enum Command {
+    Run,
+    ValidateRules,
+    Classify,
+    Quote,
+    ValidateZips,
+    Zone,
 }
 
-impl Counter {
-    fn new() -> Self {
-        Counter {
-            total_retail_cost: 0.0,
-            total_item_count: 0.0,
-            total_shipping_cost: 0.0,
-            total_shipping_cost_per_pound: 0.0,
+struct Options {
+    command: Command,
+    input_path: String,
+    config_path: String,
+    profile_name: String,
+    profiles_path: String,
+    rules_path: String,
+    zip_prefixes_path: String,
+    zone_chart_path: String,
+    origin_zip: String,
+    group_by: Vec<Dimension>,
+    level: Level,
+    area: Area,
+    zip: String,
+    service: String,
+    ship_date: String,
+}
+
+impl Options {
+    fn from_args(args: impl Iterator<Item = String>) -> Result<Options, Box<dyn Error>> {
+        let mut options = Options {
+            command: Command::Run,
+            input_path: String::from("input.csv"),
+            config_path: config::DEFAULT_CONFIG_PATH.to_string(),
+            profile_name: profiles::DEFAULT_PROFILE_NAME.to_string(),
+            profiles_path: profiles::DEFAULT_PROFILES_PATH.to_string(),
+            rules_path: classification::DEFAULT_RULES_PATH.to_string(),
+            zip_prefixes_path: zip_prefixes::DEFAULT_ZIP_PREFIXES_PATH.to_string(),
+            zone_chart_path: zones::DEFAULT_ZONE_CHART_PATH.to_string(),
+            origin_zip: String::new(),
+            group_by: vec![],
+            level: Level::default(),
+            area: Area::default(),
+            zip: String::new(),
+            service: String::new(),
+            ship_date: String::new(),
+        };
+
+        let mut args = args.peekable();
+        if let Some(command) = args.next_if(|arg| !arg.starts_with("--")) {
+            options.command = match command.as_str() {
+                "run" => Command::Run,
+                "validate-rules" => Command::ValidateRules,
+                "classify" => Command::Classify,
+                "quote" => Command::Quote,
+                "validate-zips" => Command::ValidateZips,
+                "zone" => Command::Zone,
+                _ => {
+                    return Err(Box::new(UnexpectedError {
+                        message: format!("Unknown command \"{}\".", command),
+                    }))
+                }
+            };
+        }
+
+        while let Some(arg) = args.next() {
+            if arg == "--group-by" {
+                let value = args.next().ok_or_else(|| UnexpectedError {
+                    message: format!("Missing value for {}.", arg),
+                })?;
+                options.group_by = Dimension::list_from_str(&value)?;
+                continue;
+            }
+            if arg == "--level" {
+                let value = args.next().ok_or_else(|| UnexpectedError {
+                    message: format!("Missing value for {}.", arg),
+                })?;
+                options.level = Level::from_name(&value)?;
+                continue;
+            }
+            if arg == "--area" {
+                let value = args.next().ok_or_else(|| UnexpectedError {
+                    message: format!("Missing value for {}.", arg),
+                })?;
+                options.area = Area::from_name(&value)?;
+                continue;
+            }
+            let target = match arg.as_str() {
+                "--input" => &mut options.input_path,
+                "--config" => &mut options.config_path,
+                "--profile" => &mut options.profile_name,
+                "--profiles" => &mut options.profiles_path,
+                "--rules" => &mut options.rules_path,
+                "--zip-prefixes" => &mut options.zip_prefixes_path,
+                "--zone-chart" => &mut options.zone_chart_path,
+                "--origin" => &mut options.origin_zip,
+                "--zip" => &mut options.zip,
+                "--service" => &mut options.service,
+                "--ship-date" => &mut options.ship_date,
+                _ => {
+                    return Err(Box::new(UnexpectedError {
+                        message: format!("Unknown argument \"{}\".", arg),
+                    }))
+                }
+            };
+            *target = args.next().ok_or_else(|| UnexpectedError {
+                message: format!("Missing value for {}.", arg),
+            })?;
         }
+
+        Ok(options)
     }
+}
 
-    fn update(&mut self, retail_cost: f32, shipping_cost: f32, shipping_cost_per_pound: f32) {
-        self.total_retail_cost += retail_cost;
-        self.total_item_count += 1.0;
-        self.total_shipping_cost += shipping_cost;
-        self.total_shipping_cost_per_pound += shipping_cost_per_pound;
+/// Prints the delivery window of one shipment from the transit table.
+fn quote(options: &Options) -> Result<(), Box<dyn Error>> {
+    if options.zip.is_empty() || options.service.is_empty() {
+        return Err(Box::new(UnexpectedError {
+            message: "quote needs --zip and --service.".to_string(),
+        }));
     }
+    let config = config::load_config(&options.config_path)?;
+    let rules = ServiceRules::load(&options.rules_path)?;
+    let geography = Geography::load(options, &config)?;
+
+    let classification = rules.classify(&options.service);
+    let region = Region::from_string_zip(&options.zip, &geography);
+    let state = Province::from_destination(Some(&Country::us()), &options.zip, &geography.zips);
+    let zone = geography
+        .zones
+        .zone_for(state, &options.zip)
+        .map(|zone| format!(", zone {}", zone))
+        .unwrap_or_default();
+    let ship_date = if options.ship_date.is_empty() {
+        chrono::Local::now().date_naive()
+    } else {
+        chrono::NaiveDate::parse_from_str(&options.ship_date, "%Y-%m-%d")?
+    };
+
+    let range = config
+        .transit
+        .range_for(&classification.product, &classification.tier, &region)
+        .ok_or_else(|| UnexpectedError {
+            message: format!(
+                "No transit time for \"{}\" ({}) in the config.",
+                options.service,
+                classification.tier.name()
+            ),
+        })?;
+    let window = DeliveryWindow::new(ship_date, range);
+    println!(
+        "{} to {} ({}{}), shipped {}: {}-{} business days, delivered {} to {}.",
+        options.service,
+        options.zip,
+        region.name(),
+        zone,
+        ship_date,
+        range.min,
+        range.max,
+        window.earliest,
+        window.latest
+    );
+    Ok(())
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-struct AverageOutput {
-    region: Region,
-    label: String,
-    shipping_method: String,
-    avg: f32,
+/// Prints the zone from the origin to --zip.
+fn zone(options: &Options) -> Result<(), Box<dyn Error>> {
+    if options.zip.is_empty() {
+        return Err(Box::new(UnexpectedError {
+            message: "zone needs --zip.".to_string(),
+        }));
+    }
+    let config = config::load_config(&options.config_path)?;
+    let geography = Geography::load(options, &config)?;
+    if matches!(geography.zones, ZoneCalculator::NoOrigin) {
+        return Err(Box::new(UnexpectedError {
+            message: "zone needs --origin, an origin_zip in the config or a zone chart."
+                .to_string(),
+        }));
+    }
+
+    let state = Province::from_destination(Some(&Country::us()), &options.zip, &geography.zips);
+    match geography.zones.zone_for(state, &options.zip) {
+        Some(zone) => println!("{}: zone {}.", options.zip, zone),
+        None => println!("{}: no zone.", options.zip),
+    }
+    Ok(())
 }
 
-fn report_shipping_method(shipping_method: &ShippingMethod) -> bool {
-    match shipping_method {
-        ShippingMethod::Economy => true,
-        ShippingMethod::Ground => true,
-        _ => false
+fn validate_zips(options: &Options) -> Result<(), Box<dyn Error>> {
+    let problems = ZipPrefixFile::load(&options.zip_prefixes_path)?.check();
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    if !problems.is_empty() {
+        return Err(Box::new(UnexpectedError {
+            message: format!("{} problems in the zip prefix table.", problems.len()),
+        }));
     }
+    println!("No problems in the zip prefix table.");
+    Ok(())
 }
 
-async fn run() -> Result<(), Box<dyn Error>> {
-    let file_path = "input.csv";
-    let file = fs::File::open(file_path)?;
+fn validate_rules(options: &Options) -> Result<(), Box<dyn Error>> {
+    let rules = ServiceRules::load(&options.rules_path)?;
+    let problems = rules.validate();
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    if !problems.is_empty() {
+        return Err(Box::new(UnexpectedError {
+            message: format!("{} problems in the service rules.", problems.len()),
+        }));
+    }
+    println!("No problems in the service rules.");
+    Ok(())
+}
 
-    let mut zip_code_index: Option<usize> = None;
-    let mut shipping_method_index: Option<usize> = None;
-    let mut label_cost_index: Option<usize> = None;
-    let mut packaging_cost_index: Option<usize> = None;
-    let mut labor_cost_index: Option<usize> = None;
-    let mut ship_weight_index: Option<usize> = None;
-    let mut retail_value_index: Option<usize> = None;
+fn open_input(
+    options: &Options,
+) -> Result<(csv::Reader<fs::File>, profiles::ColumnIndexes), Box<dyn Error>> {
+    let profile = profiles::load_profile(&options.profiles_path, &options.profile_name)?;
+    let file = fs::File::open(&options.input_path)?;
 
     let mut rdr = csv::Reader::from_reader(file);
-    {
-        // We nest this call in its own scope because of lifetimes.
-        let headers = rdr.headers()?;
-        for (index, header_string) in headers.iter().enumerate() {
-            match header_string {
-                "Labor (Pick/Pack) Spend" => labor_cost_index = Some(index),
-                "Carrier Service" => shipping_method_index = Some(index),
-                "Label (Carrier) Spend" => label_cost_index = Some(index),
-                "Weight of Units Shipped (lbs)" => ship_weight_index = Some(index),
-                "Material (Packaging) Spend" => packaging_cost_index = Some(index),
-                "Retail Value (Ref)" => retail_value_index = Some(index),
-                "Recipient Zip" => zip_code_index = Some(index),
-                _ => {}
-            }
-        }
+    // Runs only count the label cost, so missing packaging and labor columns
+    // aren't worth a warning.
+    let columns = profile.resolve(rdr.headers()?)?;
+    Ok((rdr, columns))
+}
+
+fn classify_services(options: &Options) -> Result<(), Box<dyn Error>> {
+    // Unlike a run, classifying may start a new rules file.
+    let mut rules_file = if Path::new(&options.rules_path).exists() {
+        classification::read_rules_file(&options.rules_path)?
+    } else {
+        RulesFile::default()
+    };
+    let rules = ServiceRules::with_file(rules_file.clone())?;
+    let (mut rdr, columns) = open_input(options)?;
+
+    let mut unmapped_services = UnmappedReport::default();
+    for result in rdr.records() {
+        let input = columns.read(&result?);
+        unmapped_services.record(&rules.tier(&input.shipping_method), &input);
+    }
+    let services: Vec<UnmappedService> = unmapped_services
+        .services(&Suggester::new(&rules))
+        .into_iter()
+        .filter(|service| service.tier == ShippingMethod::Error)
+        .collect();
+    if services.is_empty() {
+        println!(
+            "Every carrier service in {} is recognised.",
+            options.input_path
+        );
+        return Ok(());
     }
 
-    let zip_code_index = zip_code_index.unwrap();
-    let ship_weight_index = ship_weight_index.unwrap();
-    let shipping_method_index = shipping_method_index.unwrap();
-    let label_cost_index = label_cost_index.unwrap();
-    let packaging_cost_index = packaging_cost_index.unwrap();
-    let labor_cost_index = labor_cost_index.unwrap();
-    let retail_value_index = retail_value_index.unwrap();
+    let saved =
+        classify::classify_interactively(&services, io::stdin().lock(), io::stdout(), |rule| {
+            rules_file.rules.push(rule);
+            classification::write_rules_file(&options.rules_path, &rules_file)
+        })?;
+    println!("\n{} rules saved to {}.", saved, options.rules_path);
+    Ok(())
+}
+
+async fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    let config = config::load_config(&options.config_path)?;
+    let rules = ServiceRules::load(&options.rules_path)?;
+    let geography = Geography::load(&options, &config)?;
+    let by_zone = options.area == Area::Zone || options.group_by.contains(&Dimension::Zone);
+    if by_zone && matches!(geography.zones, ZoneCalculator::NoOrigin) {
+        return Err(Box::new(UnexpectedError {
+            message:
+                "Grouping by zone needs --origin, an origin_zip in the config or a zone chart."
+                    .to_string(),
+        }));
+    }
+    let suggester = Suggester::new(&rules);
+    let (mut rdr, columns) = open_input(&options)?;
 
     let mut parsed_orders: Vec<Order> = vec![];
-    let mut errors: Vec<OrderFromCSVInput> = vec![];
+    let mut errors: Vec<RowIssue> = vec![];
+    let mut quarantine_summary = QuarantineSummary::default();
+    let mut unmapped_services = UnmappedReport::default();
+    let mut auto_applied = 0;
+    // Orders waiting for tier inference, with the indexes of their issues.
+    let mut to_infer: Vec<(Order, Vec<usize>)> = vec![];
 
     for result in rdr.records() {
         let record = result?;
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
 
-        let order_from_csv_input = OrderFromCSVInput {
-            zip: record.get(zip_code_index).unwrap().to_owned(),
-            retail_value: record.get(retail_value_index).unwrap_or("").to_owned(),
-            ship_weight: record.get(ship_weight_index).unwrap().to_owned(),
-            shipping_method: record.get(shipping_method_index).unwrap().to_owned(),
-            label_cost: record.get(label_cost_index).unwrap_or("").to_owned(),
-            packaging_cost: record.get(packaging_cost_index).unwrap_or("").to_owned(),
-            labor_cost: record.get(labor_cost_index).unwrap_or("").to_owned(),
-        };
-
-        let order = Order::new_from_csv_without_packaging_cost(&order_from_csv_input);
+        let order_from_csv_input = columns.read(&record);
+        let classification = rules.classify(&order_from_csv_input.shipping_method);
+        if classification.flags.is_empty() {
+            unmapped_services.record(&classification.tier, &order_from_csv_input);
+        }
 
-        if let Some(order_value) = order {
-            if order_value.shipping_method == ShippingMethod::Error {
-                errors.push(order_from_csv_input);
+        let order =
+            Order::new_from_csv_without_packaging_cost(&order_from_csv_input, &rules, &geography);
+
+        match order {
+            Ok(mut order_value) => {
+                let order_issues = order_value.issues(&order_from_csv_input);
+
+                let suggestion = (order_value.shipping_method == ShippingMethod::Error)
+                    .then(|| suggester.suggest(&order_from_csv_input.shipping_method))
+                    .flatten();
+                let auto_apply = suggestion.as_ref().filter(|suggestion| {
+                    config
+                        .suggestions
+                        .auto_apply_confidence
+                        .is_some_and(|threshold| suggestion.confidence >= threshold)
+                });
+                if let Some(suggestion) = auto_apply {
+                    order_value.shipping_method = suggestion.tier.clone();
+                    auto_applied += 1;
+                }
+
+                // Flagged services are never quarantined, even without a tier.
+                let policy = config
+                    .quarantine
+                    .policy_for(&order_value.shipping_method)
+                    .filter(|_| order_value.flags.is_empty());
+                if let Some(policy) = policy {
+                    quarantine_summary.record(&order_value.shipping_method, policy);
+                }
+                let disposition = match policy {
+                    Some(QuarantinePolicy::Exclude) => Disposition::Excluded,
+                    _ => Disposition::Included,
+                };
+                let reclassified_as = match policy {
+                    Some(QuarantinePolicy::Reclassify { to }) => Some(to.clone()),
+                    _ => auto_apply.map(|suggestion| suggestion.tier.clone()),
+                };
+                let first_issue = errors.len();
+                for issue in order_issues {
+                    let mut row_issue = issue.at(line, disposition, &order_from_csv_input);
+                    row_issue.reclassified_as = reclassified_as.clone();
+                    if row_issue.reason == Reason::UnknownService {
+                        row_issue.suggestion = suggestion.clone();
+                    }
+                    errors.push(row_issue);
+                }
+                match policy {
+                    Some(QuarantinePolicy::Exclude) => continue,
+                    Some(QuarantinePolicy::Reclassify { to }) => {
+                        order_value.shipping_method = to.clone();
+                    }
+                    Some(QuarantinePolicy::Infer) => {
+                        to_infer.push((order_value, (first_issue..errors.len()).collect()));
+                        continue;
+                    }
+                    _ => {}
+                }
+                parsed_orders.push(order_value);
+            }
+            Err(issue) => {
+                errors.push(issue.at(line, Disposition::Excluded, &order_from_csv_input));
             }
-            parsed_orders.push(order_value);
-        } else {
-            errors.push(order_from_csv_input);
         }
     }
-    write_to_output_file(&parsed_orders)?;
-    write_to_error_file(&errors)?;
-
-    let mut cost_rate_counter: HashMap<(Region, ShippingMethod), Counter> = HashMap::new();
-    let mut per_pound_rate_counter: HashMap<(Region, ShippingMethod), Counter> = HashMap::new();
-    let mut shipping_rate_counter: HashMap<(Region, WeightRange, ShippingMethod), Counter> =
-        HashMap::new();
-    for order in parsed_orders {
-        cost_rate_counter
-            .entry((order.region.clone(), order.shipping_method.clone()))
-            .and_modify(|counter| {
-                counter.update(
-                    order.retail_value,
-                    order.shipping_cost,
-                    order.shipping_cost_per_pound,
-                )
-            })
-            .or_insert_with(|| Counter::new());
-
-        per_pound_rate_counter
-            .entry((order.region.clone(), order.shipping_method.clone()))
-            .and_modify(|counter| {
-                counter.update(
-                    order.retail_value,
-                    order.shipping_cost,
-                    order.shipping_cost_per_pound,
-                )
-            })
-            .or_insert_with(|| Counter::new());
-
-        shipping_rate_counter
-            .entry((
-                order.region.clone(),
-                order.ship_weight.clone(),
-                order.shipping_method.clone(),
-            ))
-            .and_modify(|counter| {
-                counter.update(
-                    order.retail_value,
-                    order.shipping_cost,
-                    order.shipping_cost_per_pound,
-                )
-            })
-            .or_insert_with(|| Counter::new());
 
-        if order.region == Region::International
-            || order.region == Region::Alaska
-            || order.region == Region::Hawaii
-        {
-            continue;
+    let counted = |order: &&Order| !config.flags.excludes(&order.flags);
+    let resolver = TierResolver::new(parsed_orders.iter().filter(counted));
+    let mut uninferred = 0;
+    for (mut order, issue_indexes) in to_infer {
+        let resolution = resolver.resolve(&order);
+        for index in issue_indexes {
+            let row_issue = &mut errors[index];
+            match &resolution {
+                Some(resolution) => {
+                    row_issue.reclassified_as = Some(resolution.tier.clone());
+                    row_issue.resolution = Some(resolution.clone());
+                }
+                None => row_issue.disposition = Disposition::Excluded,
+            }
+        }
+        match resolution {
+            Some(resolution) => {
+                order.shipping_method = resolution.tier;
+                parsed_orders.push(order);
+            }
+            None => uninferred += 1,
         }
-
-        // Region::All is for continental US
-
-        per_pound_rate_counter
-            .entry((Region::All, order.shipping_method.clone()))
-            .and_modify(|counter| {
-                counter.update(
-                    order.retail_value,
-                    order.shipping_cost,
-                    order.shipping_cost_per_pound,
-                )
-            })
-            .or_insert_with(|| Counter::new());
-
-        cost_rate_counter
-            .entry((Region::All, order.shipping_method.clone()))
-            .and_modify(|counter| {
-                counter.update(
-                    order.retail_value,
-                    order.shipping_cost,
-                    order.shipping_cost_per_pound,
-                )
-            })
-            .or_insert_with(|| Counter::new());
-
-        shipping_rate_counter
-            .entry((Region::All, order.ship_weight, order.shipping_method))
-            .and_modify(|counter| {
-                counter.update(
-                    order.retail_value,
-                    order.shipping_cost,
-                    order.shipping_cost_per_pound,
-                )
-            })
-            .or_insert_with(|| Counter::new());
     }
 
-    let mut cost_rate_avg: HashMap<(Region, ShippingMethod), f32> = HashMap::new();
-    let mut per_pound_rate_avg: HashMap<(Region, ShippingMethod), f32> = HashMap::new();
-    let mut shipping_rate_avg: HashMap<(Region, WeightRange, ShippingMethod), f32> = HashMap::new();
-
-    for (key, counter) in cost_rate_counter {
-        let avg_retail_cost = counter.total_retail_cost / counter.total_item_count;
-        let avg_shipping_cost = counter.total_shipping_cost / counter.total_item_count;
-        let cost_per_dollar = avg_shipping_cost / avg_retail_cost;
-
-        cost_rate_avg.insert(key, cost_per_dollar);
+    write_to_output_file(&parsed_orders)?;
+    write_to_error_file(&errors)?;
+    eprintln!(
+        "{} orders counted, {} rows excluded, {} issues written to errors.json.",
+        parsed_orders.len(),
+        issues::excluded_row_count(&errors),
+        errors.len()
+    );
+    for line in quarantine_summary.lines() {
+        eprintln!("{}", line);
     }
-
-    for (key, counter) in per_pound_rate_counter {
-        let cost_per_pound = counter.total_shipping_cost_per_pound / counter.total_item_count;
-        per_pound_rate_avg.insert(key, cost_per_pound);
+    if uninferred > 0 {
+        eprintln!(
+            "{} rows excluded because no tier could be inferred for them.",
+            uninferred
+        );
     }
-
-    for (key, counter) in shipping_rate_counter {
-        let avg_shipping_cost = counter.total_shipping_cost / counter.total_item_count;
-        shipping_rate_avg.insert(key, avg_shipping_cost);
+    let flagged = parsed_orders.iter().filter(|order| !counted(order)).count();
+    if flagged > 0 {
+        eprintln!(
+            "{} flagged orders ({}) left out of the averages.",
+            flagged,
+            config.flags.exclude.join(", ")
+        );
     }
-
-    let mut avgs: Vec<AverageOutput> = vec![];
-
-    for ((region, shipping_method), avg) in cost_rate_avg {
-        if !report_shipping_method(&shipping_method) {
-            continue
-        }
-        avgs.push(AverageOutput {
-            region,
-            shipping_method: shipping_method.name(),
-            label: "Cost per $".to_string(),
-            avg,
-        })
+    if auto_applied > 0 {
+        eprintln!(
+            "{} rows with an unmapped service counted under a suggested tier.",
+            auto_applied
+        );
     }
-
-    for ((region, shipping_method), avg) in per_pound_rate_avg {
-        if !report_shipping_method(&shipping_method) {
-            continue
-        }
-        avgs.push(AverageOutput {
-            region,
-            shipping_method: shipping_method.name(),
-            label: "$ per Pound".to_string(),
-            avg,
-        })
+    write_unmapped_services_file(&unmapped_services.services(&suggester))?;
+    if unmapped_services.len() > 0 {
+        eprintln!(
+            "{} unmapped carrier services written to unmapped_services.json.",
+            unmapped_services.len()
+        );
     }
 
-    for ((region, weight_range, shipping_method), avg) in shipping_rate_avg {
-        if !report_shipping_method(&shipping_method) {
-            continue
-        }
-        avgs.push(AverageOutput {
-            region,
-            shipping_method: shipping_method.name(),
-            label: weight_range.name(),
-            avg,
-        })
+    let mut averages = Averages::new(options.level, options.area, options.group_by.clone());
+    for order in parsed_orders.iter().filter(counted) {
+        averages.add(order);
     }
-
-
-
-    avgs.sort_by_key(|k| k.region.name());
+    let avgs = averages.outputs();
 
     write_avgs_to_output_file(&avgs)?;
     write_to_csv(&avgs)?;
 
+    if !config.transit.is_empty() {
+        let mut transit_report = TransitReport::default();
+        for order in parsed_orders.iter().filter(counted) {
+            transit_report.add(order, &config.transit);
+        }
+        write_transit_to_output_file(&transit_report.outputs())?;
+    }
+
     Ok(())
 }
 
 #[tokio::main]
 async fn main() {
-    let result = run();
-    match result.await {
-        Ok(result) => (),
-        Err(err) => eprintln!("Error: {:?}", err),
+    let result = match Options::from_args(env::args().skip(1)) {
+        Ok(options) if options.command == Command::ValidateRules => validate_rules(&options),
+        Ok(options) if options.command == Command::Classify => classify_services(&options),
+        Ok(options) if options.command == Command::Quote => quote(&options),
+        Ok(options) if options.command == Command::ValidateZips => validate_zips(&options),
+        Ok(options) if options.command == Command::Zone => zone(&options),
+        Ok(options) => run(options).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
     }
 }
 
@@ -764,27 +1443,152 @@ async fn main() {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_missing_files_are_only_optional_at_their_default_path() {
+        let missing = "no-such-file.json";
+        assert_eq!(read_optional(missing, missing).unwrap(), None);
+        assert!(read_optional(missing, "config.json").is_err());
+        assert!(read_optional("Cargo.toml", "config.json")
+            .unwrap()
+            .is_some_and(|contents| contents.contains("[package]")));
+    }
+
     #[test]
     fn test_zip_to_region_standard() {
-        let region = Region::from_string_zip("10016").ok();
+        let region = Region::try_from_string_zip("10016", &Geography::built_in()).ok();
         assert_eq!(region, Some(Region::Northeast));
     }
 
     #[test]
     fn test_invalid_zips() {
-        let region_1 = Region::from_string_zip("203000-");
-        let region_2 = Region::from_string_zip("");
-        assert!(!region_1.is_ok());
-        assert!(!region_2.is_ok());
+        let region_1 = Region::try_from_string_zip("203000-", &Geography::built_in());
+        let region_2 = Region::try_from_string_zip("", &Geography::built_in());
+        assert!(region_1.is_err());
+        assert!(region_2.is_err());
+    }
+
+    #[test]
+    fn test_territories_and_military() {
+        let region = |zip: &str| Region::try_from_string_zip(zip, &Geography::built_in()).ok();
+        assert_eq!(region("00802"), Some(Region::Territories));
+        assert_eq!(region("96910"), Some(Region::Territories));
+        assert_eq!(region("96799"), Some(Region::Territories));
+        assert_eq!(region("96950"), Some(Region::Territories));
+        assert_eq!(region("09012"), Some(Region::Military));
+        assert_eq!(region("34020"), Some(Region::Military));
+        assert_eq!(region("96601"), Some(Region::Military));
+        assert_eq!(region("96701"), Some(Region::Hawaii));
     }
 
     #[test]
     fn test_split_zips() {
-        let region_1 = Region::from_string_zip("20044-2932").ok();
-        let region_2 = Region::from_string_zip("95060-9412").ok();
+        let region_1 = Region::try_from_string_zip("20044-2932", &Geography::built_in()).ok();
+        let region_2 = Region::try_from_string_zip("95060-9412", &Geography::built_in()).ok();
         assert_eq!(region_1, Some(Region::MidAtlantic));
         assert_eq!(region_2, Some(Region::WestCoast));
+
+        let region_3 = Region::try_from_string_zip("100161234", &Geography::built_in()).ok();
+        let region_4 = Region::try_from_string_zip("021011234", &Geography::built_in()).ok();
+        assert_eq!(region_3, Some(Region::Northeast));
+        assert_eq!(region_4, Some(Region::Northeast));
+        assert!(Region::try_from_string_zip("1001612", &Geography::built_in()).is_err());
+    }
+
+    #[test]
+    fn test_rejected_rows_name_the_failing_field() {
+        let issue = Order::new_from_csv_without_packaging_cost(
+            &OrderFromCSVInput::row("10016", "UPS SurePost", "abc", "5", "50"),
+            &ServiceRules::built_in(),
+            &Geography::built_in(),
+        )
+        .unwrap_err();
+        assert_eq!(issue.field, Field::ShipWeight);
+        assert_eq!(issue.reason, Reason::UnparseableWeight);
+
+        let issue = Order::new_from_csv_without_packaging_cost(
+            &OrderFromCSVInput::row("10016", "UPS SurePost", "0", "5", "50"),
+            &ServiceRules::built_in(),
+            &Geography::built_in(),
+        )
+        .unwrap_err();
+        assert_eq!(issue.reason, Reason::ZeroWeight);
+
+        let issue = Order::new_from_csv_without_packaging_cost(
+            &OrderFromCSVInput::row("10016", "UPS SurePost", "1", "N/A", "50"),
+            &ServiceRules::built_in(),
+            &Geography::built_in(),
+        )
+        .unwrap_err();
+        assert_eq!(issue.field, Field::LabelCost);
+        assert_eq!(issue.reason, Reason::UnparseableMoney);
+    }
+
+    #[test]
+    fn test_packaging_and_labor_costs_are_added_when_asked() {
+        let mut row = OrderFromCSVInput::row("10016", "UPS SurePost", "2", "5", "50");
+        row.packaging_cost = "1.50".to_string();
+        row.labor_cost = "2".to_string();
+        let rules = ServiceRules::built_in();
+        let geography = Geography::built_in();
+        let order = Order::new_from_csv(&row, &rules, &geography).unwrap();
+        assert_eq!(order.shipping_cost.to_f64(), 8.5);
+        assert_eq!(order.shipping_cost_per_pound, 4.25);
+        let order = Order::new_from_csv_without_packaging_cost(&row, &rules, &geography).unwrap();
+        assert_eq!(order.shipping_cost.to_f64(), 5.0);
     }
 
-    // You can add more tests here
+    #[test]
+    fn test_suspicious_rows_are_flagged() {
+        let row = OrderFromCSVInput::row("K1A 0B1", "Brand New Service", "1", "5", "50");
+        let order = row.to_order();
+        let reasons: Vec<Reason> = order.issues(&row).iter().map(|i| i.reason).collect();
+        assert_eq!(reasons, vec![Reason::UnknownService, Reason::NonUsZip]);
+
+        let row = OrderFromCSVInput::row("10016", "UPS SurePost", "1", "5", "50");
+        let order = row.to_order();
+        assert!(order.issues(&row).is_empty());
+    }
+
+    #[test]
+    fn test_foreign_postal_codes_are_international() {
+        let mut row = OrderFromCSVInput::row("10115", "DHL International", "1", "25", "50");
+        row.country = "Germany".to_string();
+        let order = row.to_order();
+        assert_eq!(order.region, Region::International);
+        assert_eq!(order.country, Country::parse("DE"));
+        assert!(order.issues(&row).is_empty());
+
+        row.country = "PR".to_string();
+        row.zip = "00901".to_string();
+        let order = row.to_order();
+        assert_eq!(order.region, Region::PuertoRico);
+
+        row.country = "Atlantis".to_string();
+        let order = row.to_order();
+        assert_eq!(order.region, Region::International);
+        let reasons: Vec<Reason> = order.issues(&row).iter().map(|i| i.reason).collect();
+        assert_eq!(reasons, vec![Reason::UnknownCountry]);
+    }
+
+    #[test]
+    fn test_tracking_number_cross_checks_the_carrier() {
+        let mut row = OrderFromCSVInput::row("10016", "UPS SurePost", "1", "5", "50");
+        row.tracking_number = "9205 5901 6491 7312 7510 89".to_string();
+        let order = row.to_order();
+        assert_eq!(order.carrier, Carrier::Ups);
+        assert_eq!(order.tracking_carrier, Some(Carrier::Usps));
+        let reasons: Vec<Reason> = order.issues(&row).iter().map(|i| i.reason).collect();
+        assert_eq!(reasons, vec![Reason::CarrierMismatch]);
+
+        let mut row = OrderFromCSVInput::row("10016", "Misc Transfer Carrier", "1", "5", "50");
+        row.tracking_number = "1Z999AA10123456784".to_string();
+        let order = row.to_order();
+        assert_eq!(order.carrier, Carrier::Ups);
+        assert!(order.issues(&row).is_empty());
+
+        let mut row = OrderFromCSVInput::row("10016", "UPS", "1", "5", "50");
+        row.tracking_number = "1Z999AA10312345670".to_string();
+        let order = row.to_order();
+        assert_eq!(order.tracking_tier, Some(ShippingMethod::Ground));
+    }
 }
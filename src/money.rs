@@ -0,0 +1,320 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::error::Error;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg};
+
+// MONEY
+//
+// Amounts are held as an integer number of ten-thousandths of a dollar so that
+// summing thousands of rows stays exact and reconciles with the 3PL invoice.
+// Only ratios (cost per dollar, per pound) are computed in floating point, and
+// rounding happens when serialising.
+
+const SCALE: i64 = 10_000;
+const SCALE_DIGITS: usize = 4;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Default, Hash)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / SCALE as f64
+    }
+
+    /// `self / other`, or `None` when `other` is zero.
+    pub fn ratio(self, other: Money) -> Option<f64> {
+        if other == Money::ZERO {
+            return None;
+        }
+        Some(self.0 as f64 / other.0 as f64)
+    }
+
+    /// The amount rounded half away from zero to whole cents.
+    pub fn round_to_cents(self) -> i64 {
+        let per_cent = SCALE / 100;
+        let half = per_cent / 2;
+        if self.0 < 0 {
+            (self.0 - half) / per_cent
+        } else {
+            (self.0 + half) / per_cent
+        }
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.0 += other.0;
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cents = self.round_to_cents();
+        let sign = if cents < 0 { "-" } else { "" };
+        write!(f, "{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.round_to_cents() as f64 / 100.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        let value = f64::deserialize(deserializer)?;
+        Ok(Money((value * SCALE as f64).round() as i64))
+    }
+}
+
+/// Serialises a ratio or average rounded to four decimal places.
+pub fn serialize_rounded<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64((value * 10_000.0).round() / 10_000.0)
+}
+
+// MONEY PARSING
+//
+// Exports come out of spreadsheets, so cost cells may carry currency symbols,
+// thousands separators, accounting-style negatives or a decimal comma.
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MoneyParseError {
+    pub value: String,
+}
+
+impl fmt::Display for MoneyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unparseable amount \"{}\"", self.value)
+    }
+}
+
+impl Error for MoneyParseError {}
+
+/// Matched in any case, before or after the amount.
+const CURRENCY_CODES: [&str; 5] = ["US$", "USD", "CAD", "EUR", "GBP"];
+
+/// `text` without `code` at its start or end, ignoring case.
+fn strip_code<'a>(text: &'a str, code: &str) -> &'a str {
+    let text = match text.get(..code.len()) {
+        Some(start) if start.eq_ignore_ascii_case(code) => &text[code.len()..],
+        _ => text,
+    };
+    let end = text.len().saturating_sub(code.len());
+    match text.get(end..) {
+        Some(suffix) if suffix.eq_ignore_ascii_case(code) => &text[..end],
+        _ => text,
+    }
+}
+
+/// Parses a money cell such as "$1,234.50", "(3.20)", "1.234,56 €" or "-7".
+/// Blank cells and a lone "-" are zero. Digits past the fourth decimal place
+/// are rounded half away from zero.
+pub fn parse_money(cell: &str) -> Result<Money, MoneyParseError> {
+    let error = || MoneyParseError {
+        value: cell.to_string(),
+    };
+
+    let mut text = cell.trim();
+    if text.is_empty() || text == "-" {
+        return Ok(Money::ZERO);
+    }
+
+    let mut negative = false;
+    if let Some(inner) = text.strip_prefix('(').and_then(|t| t.strip_suffix(')')) {
+        negative = true;
+        text = inner.trim();
+    }
+    for code in CURRENCY_CODES {
+        text = strip_code(text, code).trim();
+    }
+    if let Some(rest) = text.strip_prefix('-') {
+        negative = !negative;
+        text = rest.trim();
+    } else if let Some(rest) = text.strip_suffix('-') {
+        negative = !negative;
+        text = rest.trim();
+    }
+    text = text.trim_matches(|c| matches!(c, '$' | '€' | '£')).trim();
+    if let Some(rest) = text.strip_prefix('-') {
+        negative = !negative;
+        text = rest.trim();
+    }
+
+    let number = normalize_separators(text).ok_or_else(error)?;
+    let value = to_scaled(&number).ok_or_else(error)?;
+
+    Ok(if negative { -value } else { value })
+}
+
+/// Converts a normalised unsigned decimal such as "1234.5" to `Money`
+/// without going through floating point.
+fn to_scaled(number: &str) -> Option<Money> {
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+
+    let whole = if whole.is_empty() {
+        0
+    } else {
+        whole.parse::<i64>().ok()?
+    };
+    let kept = &fraction[..fraction.len().min(SCALE_DIGITS)];
+    let mut scaled_fraction = format!("{:0<width$}", kept, width = SCALE_DIGITS)
+        .parse::<i64>()
+        .ok()?;
+    if fraction[kept.len()..].starts_with(|c: char| c >= '5') {
+        scaled_fraction += 1;
+    }
+
+    whole
+        .checked_mul(SCALE)?
+        .checked_add(scaled_fraction)
+        .map(Money)
+}
+
+/// Rewrites `text` to use "." as the only decimal separator and no grouping
+/// separators. When both "." and "," appear the later one is the decimal
+/// separator; a lone "," followed by exactly three digits is read as a
+/// thousands separator unless nothing but a zero comes before it ("0,500").
+/// Spaces of any kind, including the no-break spaces decimal-comma locales
+/// group thousands with, and "'" are dropped.
+fn normalize_separators(text: &str) -> Option<String> {
+    let text: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\'')
+        .collect();
+    if text.is_empty()
+        || !text
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+    {
+        return None;
+    }
+
+    let last_dot = text.rfind('.');
+    let last_comma = text.rfind(',');
+    let decimal = match (last_dot, last_comma) {
+        (Some(dot), Some(comma)) => Some(dot.max(comma)),
+        (Some(dot), None) if text.matches('.').count() == 1 => Some(dot),
+        (None, Some(comma))
+            if text.matches(',').count() == 1
+                && (text.len() - comma != 4
+                    || text[..comma].trim_start_matches('0').is_empty()) =>
+        {
+            Some(comma)
+        }
+        _ => None,
+    };
+
+    let decimal_char = decimal.and_then(|index| text[index..].chars().next());
+    let mut normalized = String::with_capacity(text.len());
+    for (index, c) in text.char_indices() {
+        if Some(index) == decimal {
+            normalized.push('.');
+        } else if c.is_ascii_digit() {
+            normalized.push(c);
+        } else if decimal.is_some_and(|decimal| index > decimal) || Some(c) == decimal_char {
+            // Grouping separators differ from the decimal separator and never
+            // follow it.
+            return None;
+        }
+    }
+
+    Some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cents(cents: i64) -> Result<Money, MoneyParseError> {
+        Ok(Money(cents * 100))
+    }
+
+    #[test]
+    fn test_plain_and_symbol_amounts() {
+        assert_eq!(parse_money("12.5"), cents(1250));
+        assert_eq!(parse_money("$1,234.50"), cents(123450));
+        assert_eq!(parse_money(" USD 7 "), cents(700));
+        assert_eq!(parse_money("£3"), cents(300));
+        assert_eq!(parse_money(".99"), cents(99));
+        assert_eq!(parse_money("usd 7"), cents(700));
+        assert_eq!(parse_money("7 Eur"), cents(700));
+        assert_eq!(parse_money("US$5"), cents(500));
+        assert_eq!(parse_money("us$ 5.25"), cents(525));
+    }
+
+    #[test]
+    fn test_negative_amounts() {
+        assert_eq!(parse_money("(3.20)"), cents(-320));
+        assert_eq!(parse_money("($3.20)"), cents(-320));
+        assert_eq!(parse_money("-$4.00"), cents(-400));
+        assert_eq!(parse_money("$-4.00"), cents(-400));
+        assert_eq!(parse_money("4.00-"), cents(-400));
+    }
+
+    #[test]
+    fn test_decimal_comma() {
+        assert_eq!(parse_money("1.234,56"), cents(123456));
+        assert_eq!(parse_money("1 234,56 €"), cents(123456));
+        assert_eq!(parse_money("1\u{a0}234,56\u{a0}€"), cents(123456));
+        assert_eq!(parse_money("1\u{202f}234,56 €"), cents(123456));
+        assert_eq!(parse_money("3,20"), cents(320));
+        assert_eq!(parse_money("1,234"), cents(123400));
+        assert_eq!(parse_money("0,500"), cents(50));
+        assert_eq!(parse_money(",500"), cents(50));
+        assert_eq!(parse_money("1.234.567"), cents(123456700));
+    }
+
+    #[test]
+    fn test_sub_cent_amounts_round_at_four_places() {
+        assert_eq!(parse_money("0.12345"), Ok(Money(1235)));
+        assert_eq!(parse_money("0.12344"), Ok(Money(1234)));
+        assert_eq!(parse_money("0.125").unwrap().round_to_cents(), 13);
+        assert_eq!(Money(-1250).round_to_cents(), -13);
+    }
+
+    #[test]
+    fn test_sums_are_exact() {
+        let total: Money = (0..10_000).map(|_| parse_money("0.10").unwrap()).sum();
+        assert_eq!(total, Money(100_000 * 100));
+        assert_eq!(total.to_string(), "1000.00");
+        assert_eq!((-Money(500)).to_string(), "-0.05");
+    }
+
+    #[test]
+    fn test_blank_is_zero_and_garbage_is_an_error() {
+        assert_eq!(parse_money(""), Ok(Money::ZERO));
+        assert_eq!(parse_money(" - "), Ok(Money::ZERO));
+        assert!(parse_money("N/A").is_err());
+        assert!(parse_money("1.2.3,4,5").is_err());
+        assert!(parse_money("$").is_err());
+    }
+}
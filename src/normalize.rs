@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+// CARRIER SERVICE NORMALISATION
+//
+// The same service shows up as "UPS SurePost" and "UPS SUREPOST", with or
+// without an account tag like "[RESA]", and sometimes with a protection
+// disclaimer appended in brackets. The canonical key folds all of that away;
+// the tags and disclaimer are kept so they can still be reported on.
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct ServiceName {
+    /// Lowercase service name with brackets removed and whitespace collapsed.
+    pub key: String,
+    /// Bracketed account tags, e.g. "RESA" or "RESA JEWELRY".
+    pub tags: Vec<String>,
+    /// A bracketed "... Protection ..." disclaimer, if any.
+    pub disclaimer: Option<String>,
+}
+
+impl ServiceName {
+    pub fn parse(raw: &str) -> ServiceName {
+        let mut name = ServiceName::default();
+        let mut key = String::with_capacity(raw.len());
+        let mut rest = raw;
+
+        while let Some(open) = rest.find('[') {
+            key.push_str(&rest[..open]);
+            let after = &rest[open + 1..];
+            let Some(close) = after.find(']') else {
+                // An unterminated bracket is part of the name.
+                key.push_str(&rest[open..]);
+                rest = "";
+                break;
+            };
+            let inside = collapse_whitespace(&after[..close]);
+            if inside.to_lowercase().contains("protection") {
+                name.disclaimer = Some(inside);
+            } else if !inside.is_empty() {
+                name.tags.push(inside);
+            }
+            key.push(' ');
+            rest = &after[close + 1..];
+        }
+        key.push_str(rest);
+
+        name.key = collapse_whitespace(&key).to_lowercase();
+        name
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_and_whitespace_fold() {
+        assert_eq!(ServiceName::parse("UPS SUREPOST").key, "ups surepost");
+        assert_eq!(ServiceName::parse("  UPS   SurePost ").key, "ups surepost");
+        assert_eq!(ServiceName::parse("FEDEx").key, "fedex");
+    }
+
+    #[test]
+    fn test_tags_and_disclaimers_are_split_out() {
+        let name = ServiceName::parse("FedEx One Rate (Pak) 2-Day [RESA JEWELRY]");
+        assert_eq!(name.key, "fedex one rate (pak) 2-day");
+        assert_eq!(name.tags, vec!["RESA JEWELRY".to_string()]);
+        assert_eq!(name.disclaimer, None);
+
+        let name = ServiceName::parse(
+            "USPS First-Class Mail [Route Protection Highly Recommended Not Responsible For Lost Shipment]",
+        );
+        assert_eq!(name.key, "usps first-class mail");
+        assert!(name.tags.is_empty());
+        assert_eq!(
+            name.disclaimer.as_deref(),
+            Some("Route Protection Highly Recommended Not Responsible For Lost Shipment")
+        );
+    }
+
+    #[test]
+    fn test_unterminated_bracket_is_kept() {
+        assert_eq!(
+            ServiceName::parse("UPS Ground [RESA").key,
+            "ups ground [resa"
+        );
+    }
+}
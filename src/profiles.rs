@@ -0,0 +1,396 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::{OrderFromCSVInput, UnexpectedError};
+
+// COLUMN MAPPING PROFILES
+//
+// Each 3PL (and our own warehouse) words the billing export headers a little
+// differently. A profile maps the logical fields of `OrderFromCSVInput` to the
+// header names (or aliases) used by one of those exports.
+
+pub const DEFAULT_PROFILE_NAME: &str = "default";
+pub const DEFAULT_PROFILES_PATH: &str = "profiles.json";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Zip,
+    RetailValue,
+    ShipWeight,
+    ShippingMethod,
+    LabelCost,
+    PackagingCost,
+    LaborCost,
+    TrackingNumber,
+    Country,
+}
+
+impl Field {
+    pub const ALL: [Field; 9] = [
+        Field::Zip,
+        Field::RetailValue,
+        Field::ShipWeight,
+        Field::ShippingMethod,
+        Field::LabelCost,
+        Field::PackagingCost,
+        Field::LaborCost,
+        Field::TrackingNumber,
+        Field::Country,
+    ];
+
+    /// Optional fields are read as empty cells when their column is absent.
+    pub fn is_required(&self) -> bool {
+        !matches!(
+            self,
+            Field::PackagingCost | Field::LaborCost | Field::TrackingNumber | Field::Country
+        )
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Field::Zip => String::from("zip"),
+            Field::RetailValue => String::from("retail_value"),
+            Field::ShipWeight => String::from("ship_weight"),
+            Field::ShippingMethod => String::from("shipping_method"),
+            Field::LabelCost => String::from("label_cost"),
+            Field::PackagingCost => String::from("packaging_cost"),
+            Field::LaborCost => String::from("labor_cost"),
+            Field::TrackingNumber => String::from("tracking_number"),
+            Field::Country => String::from("country"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ColumnProfile {
+    /// Header names accepted for each field, in order of preference.
+    pub columns: HashMap<Field, Vec<String>>,
+}
+
+impl ColumnProfile {
+    /// The headers of the export from our current 3PL.
+    pub fn built_in() -> ColumnProfile {
+        let columns = [
+            (Field::LaborCost, "Labor (Pick/Pack) Spend"),
+            (Field::ShippingMethod, "Carrier Service"),
+            (Field::LabelCost, "Label (Carrier) Spend"),
+            (Field::ShipWeight, "Weight of Units Shipped (lbs)"),
+            (Field::PackagingCost, "Material (Packaging) Spend"),
+            (Field::RetailValue, "Retail Value (Ref)"),
+            (Field::Zip, "Recipient Zip"),
+            (Field::TrackingNumber, "Tracking Number"),
+            (Field::Country, "Recipient Country"),
+        ]
+        .into_iter()
+        .map(|(field, header)| (field, vec![header.to_string()]))
+        .collect();
+
+        ColumnProfile { columns }
+    }
+
+    fn aliases(&self, field: Field) -> &[String] {
+        self.columns.get(&field).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Finds the index of every field in `headers`. Headers are compared
+    /// ignoring case and surrounding whitespace. Every missing required field
+    /// is reported at once; missing optional fields are left unmapped.
+    pub fn resolve(
+        &self,
+        headers: &csv::StringRecord,
+    ) -> Result<ColumnIndexes, MissingColumnsError> {
+        let find = |field: Field| -> Option<usize> {
+            self.aliases(field).iter().find_map(|alias| {
+                headers
+                    .iter()
+                    .position(|header| header.trim().eq_ignore_ascii_case(alias.trim()))
+            })
+        };
+
+        let missing: Vec<MissingColumn> = Field::ALL
+            .iter()
+            .filter(|field| field.is_required() && find(**field).is_none())
+            .map(|field| MissingColumn {
+                field: *field,
+                expected: self.aliases(*field).to_vec(),
+                suggestion: self.closest_header(*field, headers),
+            })
+            .collect();
+        if !missing.is_empty() {
+            return Err(MissingColumnsError {
+                missing,
+                headers: headers.iter().map(String::from).collect(),
+            });
+        }
+
+        let index = |field: Field| find(field).unwrap_or_default();
+        Ok(ColumnIndexes {
+            zip: index(Field::Zip),
+            retail_value: index(Field::RetailValue),
+            ship_weight: index(Field::ShipWeight),
+            shipping_method: index(Field::ShippingMethod),
+            label_cost: index(Field::LabelCost),
+            packaging_cost: find(Field::PackagingCost),
+            labor_cost: find(Field::LaborCost),
+            tracking_number: find(Field::TrackingNumber),
+            country: find(Field::Country),
+        })
+    }
+
+    /// The header closest to any alias of `field` by edit distance, if it is
+    /// close enough to plausibly be a rewording of it.
+    fn closest_header(&self, field: Field, headers: &csv::StringRecord) -> Option<String> {
+        self.aliases(field)
+            .iter()
+            .flat_map(|alias| {
+                let alias = alias.trim().to_lowercase();
+                headers.iter().map(move |header| {
+                    let distance = edit_distance(&alias, &header.trim().to_lowercase());
+                    (distance, alias.chars().count(), header)
+                })
+            })
+            .filter(|(distance, alias_length, _)| *distance <= alias_length / 2)
+            .min_by_key(|(distance, _, _)| *distance)
+            .map(|(_, _, header)| header.to_string())
+    }
+}
+
+/// Levenshtein distance between two strings, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MissingColumn {
+    pub field: Field,
+    pub expected: Vec<String>,
+    pub suggestion: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MissingColumnsError {
+    pub missing: Vec<MissingColumn>,
+    pub headers: Vec<String>,
+}
+
+impl fmt::Display for MissingColumnsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Missing required columns:")?;
+        for column in &self.missing {
+            write!(
+                f,
+                "  {} (expected one of: \"{}\")",
+                column.field.name(),
+                column.expected.join("\", \"")
+            )?;
+            if let Some(suggestion) = &column.suggestion {
+                write!(f, ", did you mean \"{}\"?", suggestion)?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "Headers present: \"{}\"", self.headers.join("\", \""))
+    }
+}
+
+impl Error for MissingColumnsError {}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColumnIndexes {
+    zip: usize,
+    retail_value: usize,
+    ship_weight: usize,
+    shipping_method: usize,
+    label_cost: usize,
+    packaging_cost: Option<usize>,
+    labor_cost: Option<usize>,
+    tracking_number: Option<usize>,
+    country: Option<usize>,
+}
+
+impl ColumnIndexes {
+    pub fn read(&self, record: &csv::StringRecord) -> OrderFromCSVInput {
+        let get = |index: usize| record.get(index).unwrap_or("").to_owned();
+        let get_optional = |index: Option<usize>| index.map(get).unwrap_or_default();
+
+        OrderFromCSVInput {
+            zip: get(self.zip),
+            retail_value: get(self.retail_value),
+            ship_weight: get(self.ship_weight),
+            shipping_method: get(self.shipping_method),
+            label_cost: get(self.label_cost),
+            packaging_cost: get_optional(self.packaging_cost),
+            labor_cost: get_optional(self.labor_cost),
+            tracking_number: get_optional(self.tracking_number),
+            country: get_optional(self.country),
+        }
+    }
+}
+
+/// Loads the profile called `name`. Profiles are read from the JSON file at
+/// `path`, which maps profile names to profiles; the built-in profile is
+/// always available as "default" unless the file overrides it.
+pub fn load_profile(path: &str, name: &str) -> Result<ColumnProfile, Box<dyn Error>> {
+    let mut profiles: HashMap<String, ColumnProfile> = HashMap::new();
+    profiles.insert(DEFAULT_PROFILE_NAME.to_string(), ColumnProfile::built_in());
+
+    if let Some(contents) = crate::read_optional(path, DEFAULT_PROFILES_PATH)? {
+        let from_file: HashMap<String, ColumnProfile> = serde_json::from_str(&contents)?;
+        profiles.extend(from_file);
+    }
+
+    profiles.remove(name).ok_or_else(|| {
+        let mut known: Vec<String> = profiles.into_keys().collect();
+        known.push(DEFAULT_PROFILE_NAME.to_string());
+        known.sort();
+        known.dedup();
+        Box::new(UnexpectedError {
+            message: format!(
+                "Unknown column profile \"{}\". Known profiles: {}",
+                name,
+                known.join(", ")
+            ),
+        }) as Box<dyn Error>
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(names: &[&str]) -> csv::StringRecord {
+        csv::StringRecord::from(names.to_vec())
+    }
+
+    #[test]
+    fn test_built_in_profile_resolves_current_export() {
+        let headers = headers(&[
+            "Order ID",
+            "Recipient Zip",
+            "Carrier Service",
+            "Weight of Units Shipped (lbs)",
+            "Label (Carrier) Spend",
+            "Material (Packaging) Spend",
+            "Labor (Pick/Pack) Spend",
+            "Retail Value (Ref)",
+        ]);
+        let indexes = ColumnProfile::built_in().resolve(&headers).unwrap();
+        let record = csv::StringRecord::from(vec![
+            "1",
+            "10016",
+            "UPS SurePost",
+            "1.5",
+            "7.10",
+            "0.40",
+            "2.25",
+            "89.00",
+        ]);
+        let input = indexes.read(&record);
+        assert_eq!(input.zip, "10016");
+        assert_eq!(input.shipping_method, "UPS SurePost");
+        assert_eq!(input.labor_cost, "2.25");
+        assert_eq!(input.retail_value, "89.00");
+    }
+
+    #[test]
+    fn test_aliases_match_ignoring_case() {
+        let profile: ColumnProfile = serde_json::from_str(
+            r#"{"columns": {
+                "zip": ["Ship To Postal Code", "Zip"],
+                "retail_value": ["Order Value"],
+                "ship_weight": ["Weight"],
+                "shipping_method": ["Service"],
+                "label_cost": ["Postage"],
+                "packaging_cost": ["Materials"],
+                "labor_cost": ["Pick Pack"]
+            }}"#,
+        )
+        .unwrap();
+        let headers = headers(&[
+            "service",
+            "ZIP",
+            "Weight",
+            "Postage",
+            "Materials",
+            "Pick Pack",
+            " Order Value ",
+        ]);
+        let indexes = profile.resolve(&headers).unwrap();
+        let record =
+            csv::StringRecord::from(vec!["FedEx Ground", "95060", "3", "9", "1", "2", "40"]);
+        let input = indexes.read(&record);
+        assert_eq!(input.zip, "95060");
+        assert_eq!(input.shipping_method, "FedEx Ground");
+        assert_eq!(input.retail_value, "40");
+    }
+
+    #[test]
+    fn test_missing_columns_are_all_reported_with_suggestions() {
+        let headers = headers(&[
+            "Recipient Zip Code",
+            "Carrier Service Name",
+            "Weight of Units Shipped (lbs)",
+            "Retail Value (Ref)",
+            "Order ID",
+        ]);
+        let err = ColumnProfile::built_in().resolve(&headers).unwrap_err();
+        let missing: Vec<Field> = err.missing.iter().map(|column| column.field).collect();
+        assert_eq!(
+            missing,
+            vec![Field::Zip, Field::ShippingMethod, Field::LabelCost]
+        );
+        assert_eq!(
+            err.missing[0].suggestion.as_deref(),
+            Some("Recipient Zip Code")
+        );
+        assert_eq!(
+            err.missing[1].suggestion.as_deref(),
+            Some("Carrier Service Name")
+        );
+        assert_eq!(err.missing[2].suggestion, None);
+        assert_eq!(err.headers.len(), 5);
+    }
+
+    #[test]
+    fn test_optional_columns_degrade_to_empty() {
+        let headers = headers(&[
+            "Recipient Zip",
+            "Carrier Service",
+            "Weight of Units Shipped (lbs)",
+            "Label (Carrier) Spend",
+            "Retail Value (Ref)",
+        ]);
+        let indexes = ColumnProfile::built_in().resolve(&headers).unwrap();
+        assert_eq!(indexes.packaging_cost, None);
+        assert_eq!(indexes.labor_cost, None);
+        let record = csv::StringRecord::from(vec!["10016", "UPS SurePost", "1", "7", "50"]);
+        let input = indexes.read(&record);
+        assert_eq!(input.packaging_cost, "");
+        assert_eq!(input.labor_cost, "");
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("zip", "zip"), 0);
+    }
+
+    #[test]
+    fn test_unknown_profile() {
+        assert!(load_profile(DEFAULT_PROFILES_PATH, "does-not-exist").is_err());
+        assert!(load_profile(DEFAULT_PROFILES_PATH, DEFAULT_PROFILE_NAME).is_ok());
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::ShippingMethod;
+
+// QUARANTINE
+//
+// Rows whose service maps to `ShippingMethod::Error` (not in the table) or
+// `ShippingMethod::Unknown` (a bare carrier name) can't be trusted in the
+// averages we hand to finance. Each class gets its own policy.
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum QuarantinePolicy {
+    Include,
+    Exclude,
+    /// Count the row as if its service were `to`.
+    Reclassify {
+        to: ShippingMethod,
+    },
+    /// Infer the tier from orders with a known tier that look alike; rows
+    /// with nothing to compare against are excluded.
+    Infer,
+}
+
+impl QuarantinePolicy {
+    pub fn name(&self) -> String {
+        match self {
+            QuarantinePolicy::Include => String::from("included"),
+            QuarantinePolicy::Exclude => String::from("excluded"),
+            QuarantinePolicy::Reclassify { to } => format!("reclassified as {}", to.name()),
+            QuarantinePolicy::Infer => String::from("sent to tier inference"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct QuarantineConfig {
+    pub error: QuarantinePolicy,
+    pub unknown: QuarantinePolicy,
+}
+
+impl Default for QuarantineConfig {
+    fn default() -> Self {
+        QuarantineConfig {
+            error: QuarantinePolicy::Exclude,
+            unknown: QuarantinePolicy::Exclude,
+        }
+    }
+}
+
+impl QuarantineConfig {
+    /// The policy for `method`, or `None` if rows with it are never
+    /// quarantined.
+    pub fn policy_for(&self, method: &ShippingMethod) -> Option<&QuarantinePolicy> {
+        match method {
+            ShippingMethod::Error => Some(&self.error),
+            ShippingMethod::Unknown => Some(&self.unknown),
+            _ => None,
+        }
+    }
+}
+
+/// How many rows of each quarantined class each policy affected.
+#[derive(Debug, Default)]
+pub struct QuarantineSummary {
+    counts: HashMap<ShippingMethod, (QuarantinePolicy, usize)>,
+}
+
+impl QuarantineSummary {
+    pub fn record(&mut self, method: &ShippingMethod, policy: &QuarantinePolicy) {
+        self.counts
+            .entry(method.clone())
+            .or_insert_with(|| (policy.clone(), 0))
+            .1 += 1;
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .counts
+            .iter()
+            .map(|(method, (policy, count))| {
+                format!(
+                    "{} rows with a {} service {}.",
+                    count,
+                    method.name(),
+                    policy.name()
+                )
+            })
+            .collect();
+        lines.sort();
+        lines
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{Province, Region, UnexpectedError};
+
+// REGION SCHEMES
+//
+// A region scheme names groups of states and territories. The "default"
+// scheme in src/region_schemes.json is the grouping averages have always
+// used; "census" follows the Census Bureau's divisions. The "regions" section
+// of the config picks a scheme by name and may define more of them. Region
+// names that match a built-in region ("Midwest", "Hawaii") keep meaning it,
+// so transit overrides for them still apply.
+
+pub const DEFAULT_SCHEME_NAME: &str = "default";
+
+/// Region name to the state and territory codes in it.
+pub type RegionScheme = BTreeMap<String, Vec<String>>;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct RegionConfig {
+    /// The scheme to use, built-in or from `schemes`.
+    pub scheme: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub schemes: BTreeMap<String, RegionScheme>,
+}
+
+impl Default for RegionConfig {
+    fn default() -> RegionConfig {
+        RegionConfig {
+            scheme: DEFAULT_SCHEME_NAME.to_string(),
+            schemes: BTreeMap::new(),
+        }
+    }
+}
+
+pub fn built_in_schemes() -> BTreeMap<String, RegionScheme> {
+    serde_json::from_str(include_str!("region_schemes.json"))
+        .expect("built-in region schemes are valid")
+}
+
+impl RegionConfig {
+    /// The selected scheme. Schemes in the config win over built-in ones of
+    /// the same name.
+    pub fn selected(&self) -> Result<RegionMap, UnexpectedError> {
+        let scheme = match self.schemes.get(&self.scheme) {
+            Some(scheme) => scheme.clone(),
+            None => built_in_schemes()
+                .remove(&self.scheme)
+                .ok_or_else(|| UnexpectedError {
+                    message: format!("Unknown region scheme \"{}\".", self.scheme),
+                })?,
+        };
+        RegionMap::new(&scheme)
+    }
+}
+
+pub struct RegionMap {
+    regions: HashMap<Province, Region>,
+}
+
+impl RegionMap {
+    pub fn new(scheme: &RegionScheme) -> Result<RegionMap, UnexpectedError> {
+        let mut regions = HashMap::new();
+        for (name, codes) in scheme {
+            let region = Region::from_name(name);
+            if matches!(region, Region::All | Region::International) {
+                return Err(UnexpectedError {
+                    message: format!("\"{}\" can't be used as a region name.", name),
+                });
+            }
+            for code in codes {
+                let province = Province::from_code(code).ok_or_else(|| UnexpectedError {
+                    message: format!("Unknown state code \"{}\" in region \"{}\".", code, name),
+                })?;
+                if let Some(other) = regions.insert(province, region.clone()) {
+                    return Err(UnexpectedError {
+                        message: format!(
+                            "{} is in both \"{}\" and \"{}\".",
+                            code,
+                            other.name(),
+                            name
+                        ),
+                    });
+                }
+            }
+        }
+        Ok(RegionMap { regions })
+    }
+
+    /// The region of `province`, or "Other" if the scheme leaves it out.
+    pub fn region_for(&self, province: Province) -> Region {
+        self.regions
+            .get(&province)
+            .cloned()
+            .unwrap_or_else(|| Region::Named(String::from("Other")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_schemes() {
+        let config = |scheme: &str| RegionConfig {
+            scheme: scheme.to_string(),
+            ..RegionConfig::default()
+        };
+        let default = config("default").selected().unwrap();
+        assert_eq!(default.region_for(Province::KY), Region::Southeast);
+        assert_eq!(default.region_for(Province::DC), Region::MidAtlantic);
+        assert_eq!(default.region_for(Province::GU), Region::Territories);
+
+        let census = config("census").selected().unwrap();
+        assert_eq!(
+            census.region_for(Province::KY),
+            Region::Named(String::from("East South Central"))
+        );
+        assert_eq!(census.region_for(Province::UT), Region::Mountain);
+        assert!(config("zones").selected().is_err());
+    }
+
+    #[test]
+    fn test_custom_scheme() {
+        let config: RegionConfig = serde_json::from_str(
+            r#"{
+                "scheme": "sales",
+                "schemes": { "sales": { "West": ["CA", "OR", "WA"], "Hawaii": ["HI"] } }
+            }"#,
+        )
+        .unwrap();
+        let sales = config.selected().unwrap();
+        assert_eq!(
+            sales.region_for(Province::OR),
+            Region::Named(String::from("West"))
+        );
+        assert_eq!(sales.region_for(Province::HI), Region::Hawaii);
+        assert_eq!(
+            sales.region_for(Province::TX),
+            Region::Named(String::from("Other"))
+        );
+    }
+
+    #[test]
+    fn test_invalid_schemes() {
+        let scheme = |json: &str| RegionMap::new(&serde_json::from_str(json).unwrap());
+        assert!(scheme(r#"{ "West": ["CA", "XX"] }"#).is_err());
+        assert!(scheme(r#"{ "West": ["CA"], "Pacific": ["CA"] }"#).is_err());
+        assert!(scheme(r#"{ "International": ["CA"] }"#).is_err());
+        assert!(scheme(r#"{ "West": ["CA"] }"#).is_ok());
+    }
+}
@@ -0,0 +1,219 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::money::Money;
+use crate::{Carrier, Order, Region, ShippingMethod, WeightRange};
+
+// TIER RESOLUTION
+//
+// Bare carrier names like "USPS" or "fedex" say nothing about the speed tier.
+// We infer it from orders with a known tier going by the same carrier: first
+// those to the same region in the same weight band, widening to the region,
+// then the weight band, then the carrier as a whole until some are found. The
+// tier whose average label cost is closest to the order's wins, unless the
+// tracking number's format points to one of the other tiers seen.
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct Resolution {
+    pub tier: ShippingMethod,
+    pub evidence: Vec<String>,
+}
+
+type CellKey = (Option<Region>, Option<WeightRange>, Carrier);
+
+#[derive(Default)]
+struct TierCosts {
+    totals: HashMap<ShippingMethod, (Money, u32)>,
+}
+
+fn resolvable(tier: &ShippingMethod) -> bool {
+    !matches!(tier, ShippingMethod::Unknown | ShippingMethod::Error)
+}
+
+fn describe_cell(key: &CellKey) -> String {
+    let (region, weight_range, carrier) = key;
+    let mut parts = vec![carrier.name()];
+    if let Some(region) = region {
+        parts.push(region.name());
+    }
+    if let Some(weight_range) = weight_range {
+        parts.push(weight_range.name());
+    }
+    parts.join(", ")
+}
+
+pub struct TierResolver {
+    cells: HashMap<CellKey, TierCosts>,
+}
+
+impl TierResolver {
+    /// Learns from every order in `orders` with a concrete tier.
+    pub fn new<'a>(orders: impl IntoIterator<Item = &'a Order>) -> TierResolver {
+        let mut cells: HashMap<CellKey, TierCosts> = HashMap::new();
+        for order in orders {
+            if !resolvable(&order.shipping_method) {
+                continue;
+            }
+            for key in TierResolver::keys(order) {
+                let (total, count) = cells
+                    .entry(key)
+                    .or_default()
+                    .totals
+                    .entry(order.shipping_method.clone())
+                    .or_insert((Money::ZERO, 0));
+                *total += order.shipping_cost;
+                *count += 1;
+            }
+        }
+        TierResolver { cells }
+    }
+
+    /// The cells an order belongs to, most specific first.
+    fn keys(order: &Order) -> [CellKey; 4] {
+        let region = Some(order.region.clone());
+        let weight_range = Some(order.ship_weight.clone());
+        let carrier = order.carrier.clone();
+        [
+            (region.clone(), weight_range.clone(), carrier.clone()),
+            (region, None, carrier.clone()),
+            (None, weight_range, carrier.clone()),
+            (None, None, carrier),
+        ]
+    }
+
+    pub fn resolve(&self, order: &Order) -> Option<Resolution> {
+        let (key, costs) = TierResolver::keys(order)
+            .into_iter()
+            .find_map(|key| self.cells.get(&key).map(|costs| (key, costs)))?;
+
+        let mut averages: Vec<(ShippingMethod, f64, u32)> = costs
+            .totals
+            .iter()
+            .map(|(tier, (total, count))| {
+                (tier.clone(), total.to_f64() / f64::from(*count), *count)
+            })
+            .collect();
+        averages.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // A tracking number whose format gives away the service outweighs
+        // the label cost, as long as orders of that tier were seen.
+        let tracked_tier = order
+            .tracking_tier
+            .as_ref()
+            .filter(|tracked| averages.iter().any(|(tier, _, _)| tier == *tracked));
+        let cost = order.shipping_cost.to_f64();
+        let closest = averages
+            .iter()
+            .min_by(|a, b| (a.1 - cost).abs().total_cmp(&(b.1 - cost).abs()))
+            .map(|(tier, _, _)| tier)?;
+        let tier = tracked_tier.unwrap_or(closest);
+
+        let mut evidence = vec![format!(
+            "Compared with known tiers for {}:",
+            describe_cell(&key)
+        )];
+        for (known_tier, average, count) in &averages {
+            evidence.push(format!(
+                "{} averages ${:.2} over {} orders",
+                known_tier.name(),
+                average,
+                count
+            ));
+        }
+        evidence.push(format!(
+            "Label cost ${} is closest to {}",
+            order.shipping_cost,
+            closest.name()
+        ));
+        if let Some(tracked) = tracked_tier {
+            evidence.push(format!(
+                "Tracking number format is for a {} service",
+                tracked.name()
+            ));
+        }
+
+        Some(Resolution {
+            tier: tier.clone(),
+            evidence,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OrderFromCSVInput;
+
+    #[test]
+    fn test_closest_cost_in_the_same_cell_wins() {
+        let known = [
+            OrderFromCSVInput::row("60601", "USPS First-Class Mail", "1", "4.00", "50").to_order(),
+            OrderFromCSVInput::row("60602", "USPS First-Class Mail", "1", "5.00", "50").to_order(),
+            OrderFromCSVInput::row("60603", "USPS Priority Mail", "1", "9.00", "50").to_order(),
+            OrderFromCSVInput::row("10016", "USPS Priority Mail", "1", "4.00", "50").to_order(),
+        ];
+        let resolver = TierResolver::new(&known);
+
+        let resolution = resolver
+            .resolve(&OrderFromCSVInput::row("60604", "USPS", "1", "8.00", "50").to_order())
+            .unwrap();
+        assert_eq!(resolution.tier, ShippingMethod::Express);
+        assert!(resolution.evidence[0].contains("Midwest"));
+
+        let resolution = resolver
+            .resolve(&OrderFromCSVInput::row("60604", "USPS", "1", "4.75", "50").to_order())
+            .unwrap();
+        assert_eq!(resolution.tier, ShippingMethod::Economy);
+    }
+
+    #[test]
+    fn test_widens_to_the_carrier_and_gives_up_without_data() {
+        let known =
+            [OrderFromCSVInput::row("60601", "FedEx Ground", "1", "10.00", "50").to_order()];
+        let resolver = TierResolver::new(&known);
+
+        let resolution = resolver
+            .resolve(&OrderFromCSVInput::row("95060", "fedex", "8", "30.00", "50").to_order())
+            .unwrap();
+        assert_eq!(resolution.tier, ShippingMethod::Ground);
+        assert_eq!(
+            resolution.evidence[0],
+            "Compared with known tiers for FedEx:"
+        );
+
+        assert_eq!(
+            resolver.resolve(&OrderFromCSVInput::row("95060", "UPS", "1", "9.00", "50").to_order()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_tracking_format_outweighs_the_label_cost() {
+        let known = [
+            OrderFromCSVInput::row("60601", "UPS Ground", "1", "10.00", "50").to_order(),
+            OrderFromCSVInput::row("60602", "UPS 2nd Day Air", "1", "30.00", "50").to_order(),
+        ];
+        let resolver = TierResolver::new(&known);
+
+        let mut tracked = OrderFromCSVInput::row("60604", "UPS", "1", "25.00", "50").to_order();
+        assert_eq!(
+            resolver.resolve(&tracked).unwrap().tier,
+            ShippingMethod::Express
+        );
+
+        tracked.tracking_tier = Some(ShippingMethod::Ground);
+        let resolution = resolver.resolve(&tracked).unwrap();
+        assert_eq!(resolution.tier, ShippingMethod::Ground);
+        assert_eq!(
+            resolution.evidence.last().unwrap(),
+            "Tracking number format is for a Ground service"
+        );
+
+        // A tier never seen with the carrier can't be confirmed.
+        tracked.tracking_tier = Some(ShippingMethod::Economy);
+        assert_eq!(
+            resolver.resolve(&tracked).unwrap().tier,
+            ShippingMethod::Express
+        );
+    }
+}
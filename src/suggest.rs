@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::classification::{MatchKind, ServiceRules};
+use crate::money;
+use crate::normalize::ServiceName;
+use crate::ShippingMethod;
+
+// TIER SUGGESTIONS
+//
+// For services no rule recognises, suggests the tier of the most similar known
+// service. Similarity is a weighted Jaccard index over the words of the
+// canonical keys, where rare words ("intl", "pak") count for more than common
+// ones ("fedex"), so "FedEx Intl Priority (Pak)" lands on Priority.
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct SuggestionConfig {
+    /// Suggestions at or above this confidence are applied to the row as if a
+    /// rule had matched. Never applied when unset.
+    pub auto_apply_confidence: Option<f64>,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct Suggestion {
+    pub tier: ShippingMethod,
+    /// Between 0 and 1.
+    #[serde(serialize_with = "money::serialize_rounded")]
+    pub confidence: f64,
+    /// The canonical key of the most similar known service.
+    pub similar_to: String,
+    /// The words the two have in common.
+    pub shared_words: Vec<String>,
+}
+
+fn words(key: &str) -> HashSet<String> {
+    key.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+pub struct Suggester {
+    known: Vec<(String, HashSet<String>, ShippingMethod)>,
+    document_frequency: HashMap<String, usize>,
+}
+
+impl Suggester {
+    /// Learns from the literal rules in `rules`. Regex rules and rules that
+    /// map to `Unknown` or `Error` carry no usable tier and are skipped.
+    pub fn new(rules: &ServiceRules) -> Suggester {
+        let mut known: Vec<(String, HashSet<String>, ShippingMethod)> = vec![];
+        for rule in rules.rules() {
+            if rule.kind == MatchKind::Regex
+                || matches!(rule.tier, ShippingMethod::Unknown | ShippingMethod::Error)
+            {
+                continue;
+            }
+            let key = ServiceName::parse(&rule.pattern).key;
+            if known.iter().any(|(existing, _, _)| *existing == key) {
+                continue;
+            }
+            let key_words = words(&key);
+            known.push((key, key_words, rule.tier.clone()));
+        }
+
+        let mut document_frequency: HashMap<String, usize> = HashMap::new();
+        for (_, key_words, _) in &known {
+            for word in key_words {
+                *document_frequency.entry(word.clone()).or_default() += 1;
+            }
+        }
+
+        Suggester {
+            known,
+            document_frequency,
+        }
+    }
+
+    fn weight(&self, word: &str) -> f64 {
+        let frequency = self.document_frequency.get(word).copied().unwrap_or(0);
+        (1.0 + self.known.len() as f64 / (1.0 + frequency as f64)).ln()
+    }
+
+    pub fn suggest(&self, service: &str) -> Option<Suggestion> {
+        let service_words = words(&ServiceName::parse(service).key);
+
+        self.known
+            .iter()
+            .map(|(key, key_words, tier)| {
+                let shared: f64 = service_words
+                    .intersection(key_words)
+                    .map(|word| self.weight(word))
+                    .sum();
+                let all: f64 = service_words
+                    .union(key_words)
+                    .map(|word| self.weight(word))
+                    .sum();
+                (
+                    key,
+                    key_words,
+                    tier,
+                    if all > 0.0 { shared / all } else { 0.0 },
+                )
+            })
+            .filter(|(_, _, _, similarity)| *similarity > 0.0)
+            .max_by(|a, b| a.3.total_cmp(&b.3))
+            .map(|(key, key_words, tier, similarity)| {
+                let mut shared_words: Vec<String> =
+                    service_words.intersection(key_words).cloned().collect();
+                shared_words.sort();
+                Suggestion {
+                    tier: tier.clone(),
+                    confidence: similarity,
+                    similar_to: key.clone(),
+                    shared_words,
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rare_words_decide_the_tier() {
+        let suggester = Suggester::new(&ServiceRules::built_in());
+        let suggestion = suggester.suggest("FedEx Intl Priority (Pak)").unwrap();
+        assert_eq!(suggestion.tier, ShippingMethod::Priority);
+        assert!(suggestion.shared_words.contains(&"intl".to_string()));
+        assert!(suggestion.confidence > 0.0 && suggestion.confidence < 1.0);
+    }
+
+    #[test]
+    fn test_known_service_has_full_confidence() {
+        let suggester = Suggester::new(&ServiceRules::built_in());
+        let suggestion = suggester.suggest("FEDEX GROUND").unwrap();
+        assert_eq!(suggestion.tier, ShippingMethod::Ground);
+        assert_eq!(suggestion.confidence, 1.0);
+    }
+
+    #[test]
+    fn test_nothing_in_common() {
+        let suggester = Suggester::new(&ServiceRules::built_in());
+        assert_eq!(suggester.suggest("Teleport"), None);
+    }
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+use crate::UnexpectedError;
+
+// SERVICE TAXONOMY
+//
+// Every classified service sits in a tree: the carrier, the carrier's product
+// ("fedex one rate (pak) 2-day") and the product's speed tier. Averages can be
+// reported at any level of it. Things that aren't about speed, like jewelry
+// shipments or stock transfers, are flags on the service instead. Flagged
+// orders can be kept out of everything that compares shipping costs.
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    /// One average per carrier, every concrete tier together.
+    Carrier,
+    /// One average per carrier and product.
+    Product,
+    /// One average per speed tier, across carriers.
+    #[default]
+    Tier,
+}
+
+impl Level {
+    pub fn from_name(name: &str) -> Result<Level, UnexpectedError> {
+        match name.trim() {
+            "carrier" => Ok(Level::Carrier),
+            "product" => Ok(Level::Product),
+            "tier" => Ok(Level::Tier),
+            _ => Err(UnexpectedError {
+                message: format!("Unknown level \"{}\".", name),
+            }),
+        }
+    }
+
+    pub fn has_carrier(&self) -> bool {
+        matches!(self, Level::Carrier | Level::Product)
+    }
+
+    pub fn has_product(&self) -> bool {
+        *self == Level::Product
+    }
+
+    pub fn has_tier(&self) -> bool {
+        matches!(self, Level::Product | Level::Tier)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct FlagConfig {
+    /// Orders with any of these flags are left out of the averages, transit
+    /// times and tier inference. They are still written to output.json.
+    pub exclude: Vec<String>,
+}
+
+impl Default for FlagConfig {
+    fn default() -> FlagConfig {
+        FlagConfig {
+            exclude: vec![String::from("jewelry"), String::from("transfer")],
+        }
+    }
+}
+
+impl FlagConfig {
+    pub fn excludes(&self, flags: &[String]) -> bool {
+        flags.iter().any(|flag| self.exclude.contains(flag))
+    }
+}
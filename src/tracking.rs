@@ -0,0 +1,199 @@
+use crate::Carrier;
+
+// TRACKING NUMBERS
+//
+// Each carrier's tracking numbers have a recognisable shape and a check
+// digit, so a tracking number tells us who really carried the parcel
+// whatever the "Carrier Service" text says. Numbers that fail their check
+// digit are not attributed to anyone. Some formats also give away the
+// service: UPS numbers carry a service code, and FedEx only uses 15 digit and
+// "96" numbers for Ground.
+
+/// The carrier that issued `tracking_number`, ignoring spaces, dashes and
+/// case, or `None` if it isn't a valid number in any known format.
+pub fn carrier_for(tracking_number: &str) -> Option<Carrier> {
+    let number: String = tracking_number
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_ascii_uppercase();
+
+    if number.starts_with("1Z") {
+        return is_ups(&number).then_some(Carrier::Ups);
+    }
+    if is_s10(&number) {
+        // UPU S10 numbers end in the issuing country; only USPS issues "US".
+        return number.ends_with("US").then_some(Carrier::Usps);
+    }
+    if !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    match number.len() {
+        10 if is_mod7(&number) => Some(Carrier::Dhl),
+        12 if is_mod11(&number) => Some(Carrier::FedEx),
+        15 if is_mod10(&number) => Some(Carrier::FedEx),
+        // FedEx Ground 96: the last 15 digits carry the check digit.
+        22 if number.starts_with("96") && is_mod10(&number[7..]) => Some(Carrier::FedEx),
+        20 | 22 if is_mod10(&number) => Some(Carrier::Usps),
+        // USPS with a routing prefix: "420" and a 5 digit zip or ZIP+4.
+        30 if number.starts_with("420") && is_mod10(&number[8..]) => Some(Carrier::Usps),
+        34 if number.starts_with("420") && is_mod10(&number[12..]) => Some(Carrier::Usps),
+        _ => None,
+    }
+}
+
+/// The service `tracking_number` was issued for, if its format says.
+pub fn service_for(tracking_number: &str) -> Option<&'static str> {
+    let number: String = tracking_number
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_ascii_uppercase();
+
+    match carrier_for(&number)? {
+        Carrier::Ups => match &number[8..10] {
+            "01" => Some("UPS Next Day Air"),
+            "02" => Some("UPS 2nd Day Air"),
+            "03" => Some("UPS Ground"),
+            "12" => Some("UPS 3 Day Select"),
+            "13" => Some("UPS Next Day Air Saver"),
+            _ => None,
+        },
+        Carrier::FedEx if number.len() != 12 => Some("FedEx Ground"),
+        _ => None,
+    }
+}
+
+fn digits(number: &str) -> Vec<u32> {
+    number.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+/// "1Z", 6 character shipper number, 2 digit service code, 7 digit package
+/// number and a check digit. Letters count as (ASCII code - 63) mod 10.
+fn is_ups(number: &str) -> bool {
+    if number.len() != 18 || !number.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return false;
+    }
+    let values: Vec<u32> = number[2..]
+        .chars()
+        .map(|c| c.to_digit(10).unwrap_or_else(|| (c as u32 - 63) % 10))
+        .collect();
+    let (check, body) = values.split_last().unwrap();
+    let sum: u32 = body
+        .iter()
+        .enumerate()
+        .map(|(i, value)| if i % 2 == 1 { value * 2 } else { *value })
+        .sum();
+    (10 - sum % 10) % 10 == *check
+}
+
+/// Two letters, 8 digits, a check digit and a two letter country code.
+fn is_s10(number: &str) -> bool {
+    let bytes = number.as_bytes();
+    if bytes.len() != 13
+        || !bytes[..2].iter().all(u8::is_ascii_uppercase)
+        || !bytes[2..11].iter().all(u8::is_ascii_digit)
+        || !bytes[11..].iter().all(u8::is_ascii_uppercase)
+    {
+        return false;
+    }
+    let values = digits(&number[2..11]);
+    let sum: u32 = values[..8]
+        .iter()
+        .zip([8, 6, 4, 2, 3, 5, 9, 7])
+        .map(|(value, weight)| value * weight)
+        .sum();
+    let check = match 11 - sum % 11 {
+        10 => 0,
+        11 => 5,
+        check => check,
+    };
+    check == values[8]
+}
+
+/// The first 9 digits modulo 7 give the last.
+fn is_mod7(number: &str) -> bool {
+    let Ok(body) = number[..9].parse::<u64>() else {
+        return false;
+    };
+    u64::from(digits(&number[9..])[0]) == body % 7
+}
+
+/// Weights 1, 3, 7 repeating from the right, modulo 11, then modulo 10.
+fn is_mod11(number: &str) -> bool {
+    let values = digits(number);
+    let (check, body) = values.split_last().unwrap();
+    let sum: u32 = body
+        .iter()
+        .rev()
+        .zip([1, 3, 7].into_iter().cycle())
+        .map(|(value, weight)| value * weight)
+        .sum();
+    sum % 11 % 10 == *check
+}
+
+/// Weights 3, 1 repeating from the right, as in GS1 barcodes.
+fn is_mod10(number: &str) -> bool {
+    let values = digits(number);
+    let (check, body) = values.split_last().unwrap();
+    let sum: u32 = body
+        .iter()
+        .rev()
+        .zip([3, 1].into_iter().cycle())
+        .map(|(value, weight)| value * weight)
+        .sum();
+    (10 - sum % 10) % 10 == *check
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_services_from_the_format() {
+        assert_eq!(service_for("1Z999AA10123456784"), Some("UPS Next Day Air"));
+        assert_eq!(service_for("1z 999 aa1 0312 3456 70"), Some("UPS Ground"));
+        assert_eq!(service_for("041441760228964"), Some("FedEx Ground"));
+        assert_eq!(service_for("9611020987654312345672"), Some("FedEx Ground"));
+        assert_eq!(service_for("986578788855"), None);
+        assert_eq!(service_for("9205 5901 6491 7312 7510 89"), None);
+    }
+
+    #[test]
+    fn test_each_carrier_is_recognised() {
+        assert_eq!(carrier_for("1Z999AA10123456784"), Some(Carrier::Ups));
+        assert_eq!(carrier_for("1z 999 aa1 0123 4567 84"), Some(Carrier::Ups));
+        assert_eq!(carrier_for("986578788855"), Some(Carrier::FedEx));
+        assert_eq!(carrier_for("041441760228964"), Some(Carrier::FedEx));
+        assert_eq!(carrier_for("9611020987654312345672"), Some(Carrier::FedEx));
+        assert_eq!(
+            carrier_for("9205 5901 6491 7312 7510 89"),
+            Some(Carrier::Usps)
+        );
+        assert_eq!(carrier_for("03071790000523483741"), Some(Carrier::Usps));
+        assert_eq!(
+            carrier_for("420221539101026837331000039521"),
+            Some(Carrier::Usps)
+        );
+        assert_eq!(
+            carrier_for("420 22153 1234 9101 0268 3733 1000 0395 21"),
+            Some(Carrier::Usps)
+        );
+        assert_eq!(carrier_for("4202215312349101026837331000039522"), None);
+        assert_eq!(carrier_for("EA123456785US"), Some(Carrier::Usps));
+        assert_eq!(carrier_for("3318810025"), Some(Carrier::Dhl));
+    }
+
+    #[test]
+    fn test_bad_check_digits_are_rejected() {
+        assert_eq!(carrier_for("1Z999AA10123456785"), None);
+        assert_eq!(carrier_for("986578788856"), None);
+        assert_eq!(carrier_for("9205590164917312751088"), None);
+        assert_eq!(carrier_for("EA123456786US"), None);
+        assert_eq!(carrier_for("EA123456785CA"), None);
+        assert_eq!(carrier_for("3318810026"), None);
+        assert_eq!(carrier_for(""), None);
+        assert_eq!(carrier_for("pending"), None);
+    }
+}
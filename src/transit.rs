@@ -0,0 +1,212 @@
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::money;
+use crate::{Order, Region, ShippingMethod};
+
+// TRANSIT TIMES
+//
+// How many business days each service takes, from the "transit" section of
+// the config. An entry names a product or a whole tier and may override its
+// range for particular regions. Weekends are skipped when turning a range
+// into delivery dates; holidays are not.
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct TransitRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct TransitEntry {
+    /// A product (canonical service key), e.g. "usps priority mail".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    /// Every product in a tier, for products with no entry of their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tier: Option<ShippingMethod>,
+    pub days: TransitRange,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub regions: HashMap<Region, TransitRange>,
+}
+
+impl TransitEntry {
+    fn range_in(&self, region: &Region) -> TransitRange {
+        self.regions.get(region).copied().unwrap_or(self.days)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct TransitConfig {
+    pub services: Vec<TransitEntry>,
+}
+
+impl TransitConfig {
+    pub fn is_empty(&self) -> bool {
+        self.services.is_empty()
+    }
+
+    /// The transit range of `product` in `tier` to `region`. An entry for the
+    /// product wins over one for its tier.
+    pub fn range_for(
+        &self,
+        product: &str,
+        tier: &ShippingMethod,
+        region: &Region,
+    ) -> Option<TransitRange> {
+        let for_product = self
+            .services
+            .iter()
+            .find(|entry| entry.product.as_deref() == Some(product));
+        let for_tier = || {
+            self.services
+                .iter()
+                .find(|entry| entry.product.is_none() && entry.tier.as_ref() == Some(tier))
+        };
+        for_product
+            .or_else(for_tier)
+            .map(|entry| entry.range_in(region))
+    }
+}
+
+/// The `days`th business day after `date`.
+pub fn add_business_days(date: NaiveDate, days: u32) -> NaiveDate {
+    let mut date = date;
+    let mut remaining = days;
+    while remaining > 0 {
+        date = date.succ_opt().expect("date out of range");
+        if !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            remaining -= 1;
+        }
+    }
+    date
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DeliveryWindow {
+    pub earliest: NaiveDate,
+    pub latest: NaiveDate,
+}
+
+impl DeliveryWindow {
+    pub fn new(ship_date: NaiveDate, range: TransitRange) -> DeliveryWindow {
+        DeliveryWindow {
+            earliest: add_business_days(ship_date, range.min),
+            latest: add_business_days(ship_date, range.max),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct TransitOutput {
+    pub region: Region,
+    pub shipping_method: String,
+    pub orders: u32,
+    #[serde(serialize_with = "money::serialize_rounded")]
+    pub avg_min_days: f64,
+    #[serde(serialize_with = "money::serialize_rounded")]
+    pub avg_max_days: f64,
+}
+
+/// Expected business days in transit of the counted orders, by region and
+/// tier. Orders without a transit entry are left out.
+#[derive(Default)]
+pub struct TransitReport {
+    totals: HashMap<(Region, ShippingMethod), (u32, u32, u32)>,
+}
+
+impl TransitReport {
+    pub fn add(&mut self, order: &Order, table: &TransitConfig) {
+        let Some(range) = table.range_for(&order.product, &order.shipping_method, &order.region)
+        else {
+            return;
+        };
+        let (min, max, count) = self
+            .totals
+            .entry((order.region.clone(), order.shipping_method.clone()))
+            .or_default();
+        *min += range.min;
+        *max += range.max;
+        *count += 1;
+    }
+
+    pub fn outputs(&self) -> Vec<TransitOutput> {
+        let mut outputs: Vec<TransitOutput> = self
+            .totals
+            .iter()
+            .map(|((region, tier), (min, max, count))| TransitOutput {
+                region: region.clone(),
+                shipping_method: tier.name(),
+                orders: *count,
+                avg_min_days: f64::from(*min) / f64::from(*count),
+                avg_max_days: f64::from(*max) / f64::from(*count),
+            })
+            .collect();
+        outputs.sort_by_key(|o| (o.region.name(), o.shipping_method.clone()));
+        outputs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> TransitConfig {
+        serde_json::from_str(
+            r#"{
+                "services": [
+                    { "tier": "Ground", "days": { "min": 1, "max": 5 },
+                      "regions": { "Hawaii": { "min": 5, "max": 9 } } },
+                    { "product": "fedex home delivery", "days": { "min": 2, "max": 4 } }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_product_then_tier_then_region() {
+        let table = table();
+        let range = |product: &str, region: Region| {
+            table.range_for(product, &ShippingMethod::Ground, &region)
+        };
+        assert_eq!(
+            range("ups ground", Region::Midwest),
+            Some(TransitRange { min: 1, max: 5 })
+        );
+        assert_eq!(
+            range("ups ground", Region::Hawaii),
+            Some(TransitRange { min: 5, max: 9 })
+        );
+        assert_eq!(
+            range("fedex home delivery", Region::Hawaii),
+            Some(TransitRange { min: 2, max: 4 })
+        );
+        assert_eq!(
+            table.range_for(
+                "usps priority mail",
+                &ShippingMethod::Priority,
+                &Region::Midwest
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_delivery_window_skips_weekends() {
+        // A Friday.
+        let ship_date = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let window = DeliveryWindow::new(ship_date, TransitRange { min: 1, max: 5 });
+        assert_eq!(
+            window.earliest,
+            NaiveDate::from_ymd_opt(2026, 10, 19).unwrap()
+        );
+        assert_eq!(
+            window.latest,
+            NaiveDate::from_ymd_opt(2026, 10, 23).unwrap()
+        );
+        assert_eq!(add_business_days(ship_date, 0), ship_date);
+    }
+}
@@ -0,0 +1,126 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::money::{self, Money};
+use crate::suggest::{Suggester, Suggestion};
+use crate::{OrderFromCSVInput, ShippingMethod};
+
+// UNMAPPED SERVICES
+//
+// Every distinct "Carrier Service" value that classified as Error or Unknown,
+// with enough context to decide which mapping to add first.
+
+const SAMPLE_SIZE: usize = 5;
+
+#[derive(Serialize, Debug, PartialEq, Clone)]
+pub struct UnmappedService {
+    pub service: String,
+    pub tier: ShippingMethod,
+    pub rows: u32,
+    /// Label spend of the rows whose label cost could be parsed.
+    pub label_spend: Money,
+    pub sample_zips: Vec<String>,
+    pub sample_weights: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<Suggestion>,
+}
+
+#[derive(Debug, Default)]
+pub struct UnmappedReport {
+    services: HashMap<String, UnmappedService>,
+}
+
+fn push_sample(samples: &mut Vec<String>, value: &str) {
+    let value = value.trim();
+    if samples.len() < SAMPLE_SIZE && !value.is_empty() && !samples.iter().any(|s| s == value) {
+        samples.push(value.to_string());
+    }
+}
+
+impl UnmappedReport {
+    /// Records `input` if its service classified as `tier` is unmapped.
+    pub fn record(&mut self, tier: &ShippingMethod, input: &OrderFromCSVInput) {
+        if !matches!(tier, ShippingMethod::Error | ShippingMethod::Unknown) {
+            return;
+        }
+
+        let service = self
+            .services
+            .entry(input.shipping_method.clone())
+            .or_insert_with(|| UnmappedService {
+                service: input.shipping_method.clone(),
+                tier: tier.clone(),
+                rows: 0,
+                label_spend: Money::ZERO,
+                sample_zips: vec![],
+                sample_weights: vec![],
+                suggestion: None,
+            });
+        service.rows += 1;
+        service.label_spend += money::parse_money(&input.label_cost).unwrap_or_default();
+        push_sample(&mut service.sample_zips, &input.zip);
+        push_sample(&mut service.sample_weights, &input.ship_weight);
+    }
+
+    pub fn len(&self) -> usize {
+        self.services.len()
+    }
+
+    /// The services sorted by label spend, largest first, with a suggested
+    /// tier for those no rule recognised.
+    pub fn services(&self, suggester: &Suggester) -> Vec<UnmappedService> {
+        let mut services: Vec<UnmappedService> = self.services.values().cloned().collect();
+        for service in services.iter_mut() {
+            if service.tier == ShippingMethod::Error {
+                service.suggestion = suggester.suggest(&service.service);
+            }
+        }
+        services.sort_by(|a, b| {
+            b.label_spend
+                .cmp(&a.label_spend)
+                .then(b.rows.cmp(&a.rows))
+                .then(a.service.cmp(&b.service))
+        });
+        services
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classification::ServiceRules;
+
+    #[test]
+    fn test_unmapped_services_are_sorted_by_spend() {
+        let mut report = UnmappedReport::default();
+        report.record(
+            &ShippingMethod::Error,
+            &OrderFromCSVInput::row("10016", "Pigeon", "1", "1.00", ""),
+        );
+        report.record(
+            &ShippingMethod::Error,
+            &OrderFromCSVInput::row("10016", "Pigeon", "1", "2.00", ""),
+        );
+        report.record(
+            &ShippingMethod::Unknown,
+            &OrderFromCSVInput::row("60601", "USPS", "1", "9.00", ""),
+        );
+        report.record(
+            &ShippingMethod::Ground,
+            &OrderFromCSVInput::row("60601", "FedEx Ground", "1", "90.00", ""),
+        );
+        report.record(
+            &ShippingMethod::Error,
+            &OrderFromCSVInput::row("60601", "Drone", "1", "N/A", ""),
+        );
+
+        let services = report.services(&Suggester::new(&ServiceRules::built_in()));
+        let names: Vec<&str> = services.iter().map(|s| s.service.as_str()).collect();
+        assert_eq!(names, vec!["USPS", "Pigeon", "Drone"]);
+        assert_eq!(services[1].rows, 2);
+        assert_eq!(services[1].label_spend.to_string(), "3.00");
+        assert_eq!(services[1].sample_zips, vec!["10016".to_string()]);
+        assert_eq!(services[2].label_spend, Money::ZERO);
+        assert_eq!(services[0].suggestion, None);
+    }
+}
@@ -0,0 +1,270 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+
+use crate::{Province, UnexpectedError};
+
+// ZIP PREFIXES
+//
+// The first three digits of a zip are its sectional center facility (SCF),
+// and every SCF lies in one state. The table lists the prefix ranges of each
+// state and the prefixes USPS has never assigned, plus the odd 5 digit zips
+// that belong to a different state than their prefix. A zip prefix file with
+// the same shape replaces the built-in one in src/zip_prefixes.json.
+
+pub const DEFAULT_ZIP_PREFIXES_PATH: &str = "zip_prefixes.json";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ZipPrefixFile {
+    /// State (or territory) code to prefix ranges like "010-027" or "055".
+    pub states: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub unassigned: Vec<String>,
+    /// State code to 5 digit zip ranges that override their prefix, e.g.
+    /// American Samoa's "96799" inside Hawaii's "967".
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub zips: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PrefixProblem {
+    InvalidRange {
+        range: String,
+    },
+    /// A state code in `states` or `zips` that isn't a US state or territory.
+    UnknownState {
+        code: String,
+    },
+    /// A prefix listed under more than one state, or under a state and as
+    /// unassigned.
+    Overlap {
+        prefix: u32,
+        listed_by: Vec<String>,
+    },
+    Unmapped {
+        prefix: u32,
+    },
+}
+
+impl fmt::Display for PrefixProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PrefixProblem::InvalidRange { range } => {
+                write!(f, "\"{}\" is not a prefix or a range of prefixes.", range)
+            }
+            PrefixProblem::UnknownState { code } => {
+                write!(f, "\"{}\" is not a state or territory code.", code)
+            }
+            PrefixProblem::Overlap { prefix, listed_by } => {
+                write!(f, "{:03} is listed by {}.", prefix, listed_by.join(" and "))
+            }
+            PrefixProblem::Unmapped { prefix } => {
+                write!(f, "{:03} is neither mapped nor unassigned.", prefix)
+            }
+        }
+    }
+}
+
+/// Parses "055" or "010-027" into the prefixes it covers, or with `digits`
+/// of 5, "96950-96952" into zips.
+pub fn parse_range(range: &str, digits: usize) -> Option<std::ops::RangeInclusive<u32>> {
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let parse = |prefix: &str| {
+        let prefix = prefix.trim();
+        (prefix.len() == digits)
+            .then(|| prefix.parse::<u32>().ok())
+            .flatten()
+    };
+    let (start, end) = (parse(start)?, parse(end)?);
+    (start <= end).then_some(start..=end)
+}
+
+impl ZipPrefixFile {
+    pub fn built_in() -> ZipPrefixFile {
+        serde_json::from_str(include_str!("zip_prefixes.json"))
+            .expect("built-in zip prefix table is valid")
+    }
+
+    /// Loads the file at `path`, or the built-in table if there is none.
+    pub fn load(path: &str) -> Result<ZipPrefixFile, Box<dyn Error>> {
+        match crate::read_optional(path, DEFAULT_ZIP_PREFIXES_PATH)? {
+            Some(contents) => Ok(serde_json::from_str(&contents)?),
+            None => Ok(ZipPrefixFile::built_in()),
+        }
+    }
+
+    /// Every prefix from 000 to 999 should be listed exactly once.
+    pub fn check(&self) -> Vec<PrefixProblem> {
+        let mut problems = vec![];
+        let mut listed_by: Vec<Vec<String>> = vec![vec![]; 1000];
+
+        let codes: BTreeSet<&String> = self.states.keys().chain(self.zips.keys()).collect();
+        for code in codes {
+            if Province::from_code(code).is_none() {
+                problems.push(PrefixProblem::UnknownState { code: code.clone() });
+            }
+        }
+
+        let unassigned = (String::from("unassigned"), &self.unassigned);
+        for (name, ranges) in self
+            .states
+            .iter()
+            .map(|(state, ranges)| (state.clone(), ranges))
+            .chain([unassigned])
+        {
+            for range in ranges {
+                match parse_range(range, 3) {
+                    Some(prefixes) => {
+                        for prefix in prefixes {
+                            listed_by[prefix as usize].push(name.clone());
+                        }
+                    }
+                    None => problems.push(PrefixProblem::InvalidRange {
+                        range: range.clone(),
+                    }),
+                }
+            }
+        }
+
+        for range in self.zips.values().flatten() {
+            if parse_range(range, 5).is_none() {
+                problems.push(PrefixProblem::InvalidRange {
+                    range: range.clone(),
+                });
+            }
+        }
+
+        for (prefix, names) in listed_by.into_iter().enumerate() {
+            let prefix = prefix as u32;
+            match names.len() {
+                0 => problems.push(PrefixProblem::Unmapped { prefix }),
+                1 => {}
+                _ => problems.push(PrefixProblem::Overlap {
+                    prefix,
+                    listed_by: names,
+                }),
+            }
+        }
+        problems
+    }
+}
+
+pub struct ZipPrefixTable {
+    states: Vec<Option<String>>,
+    zips: Vec<(std::ops::RangeInclusive<u32>, String)>,
+}
+
+impl ZipPrefixTable {
+    /// Where states overlap the last one alphabetically wins; `check` reports
+    /// every overlap.
+    pub fn new(file: &ZipPrefixFile) -> Result<ZipPrefixTable, UnexpectedError> {
+        let invalid = |range: &String| UnexpectedError {
+            message: format!("Invalid zip prefix range \"{}\".", range),
+        };
+        let mut states = vec![None; 1000];
+        for (state, ranges) in &file.states {
+            for range in ranges {
+                for prefix in parse_range(range, 3).ok_or_else(|| invalid(range))? {
+                    states[prefix as usize] = Some(state.clone());
+                }
+            }
+        }
+        let mut zips = vec![];
+        for (state, ranges) in &file.zips {
+            for range in ranges {
+                zips.push((
+                    parse_range(range, 5).ok_or_else(|| invalid(range))?,
+                    state.clone(),
+                ));
+            }
+        }
+        Ok(ZipPrefixTable { states, zips })
+    }
+
+    #[cfg(test)]
+    pub fn built_in() -> ZipPrefixTable {
+        ZipPrefixTable::new(&ZipPrefixFile::built_in()).expect("built-in zip prefix table is valid")
+    }
+
+    /// The state of a 5 digit zip, or `None` for unassigned prefixes.
+    pub fn state_for(&self, zip: u32) -> Option<&str> {
+        if let Some((_, state)) = self.zips.iter().find(|(zips, _)| zips.contains(&zip)) {
+            return Some(state);
+        }
+        self.states.get((zip / 100) as usize)?.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_table_is_consistent() {
+        assert_eq!(ZipPrefixFile::built_in().check(), vec![]);
+        let table = ZipPrefixTable::built_in();
+        assert_eq!(table.state_for(10016), Some("NY"));
+        assert_eq!(table.state_for(20042), Some("DC"));
+        assert_eq!(table.state_for(901), Some("PR"));
+        assert_eq!(table.state_for(21300), None);
+        assert_eq!(table.state_for(96799), Some("AS"));
+        assert_eq!(table.state_for(96798), Some("HI"));
+        assert_eq!(table.state_for(96951), Some("MP"));
+        assert_eq!(table.state_for(96910), Some("GU"));
+    }
+
+    #[test]
+    fn test_zips_in_another_state_than_their_prefix() {
+        let table = ZipPrefixTable::built_in();
+        // Fishers Island is reached from Connecticut but is in New York.
+        assert_eq!(table.state_for(6390), Some("NY"));
+        assert_eq!(table.state_for(6389), Some("CT"));
+        assert_eq!(table.state_for(75502), Some("AR"));
+        assert_eq!(table.state_for(75501), Some("TX"));
+        assert_eq!(table.state_for(71233), Some("MS"));
+        assert_eq!(table.state_for(71232), Some("LA"));
+        assert_eq!(table.state_for(20331), Some("MD"));
+        assert_eq!(table.state_for(20330), Some("DC"));
+        assert_eq!(table.state_for(20799), Some("DC"));
+        assert_eq!(table.state_for(20798), Some("MD"));
+        assert_eq!(table.state_for(68119), Some("IA"));
+        assert_eq!(table.state_for(68120), Some("IA"));
+        assert_eq!(table.state_for(68121), Some("NE"));
+    }
+
+    #[test]
+    fn test_check_reports_overlaps_and_gaps() {
+        let file: ZipPrefixFile = serde_json::from_str(
+            r#"{
+                "states": { "DC": ["200-205"], "VA": ["201", "22x"] },
+                "unassigned": ["000-198", "205-999"],
+                "zips": { "VAA": ["20101"] }
+            }"#,
+        )
+        .unwrap();
+        let problems = file.check();
+        assert_eq!(
+            problems,
+            vec![
+                PrefixProblem::UnknownState {
+                    code: "VAA".to_string()
+                },
+                PrefixProblem::InvalidRange {
+                    range: "22x".to_string()
+                },
+                PrefixProblem::Unmapped { prefix: 199 },
+                PrefixProblem::Overlap {
+                    prefix: 201,
+                    listed_by: vec!["DC".to_string(), "VA".to_string()]
+                },
+                PrefixProblem::Overlap {
+                    prefix: 205,
+                    listed_by: vec!["DC".to_string(), "unassigned".to_string()]
+                },
+            ]
+        );
+        assert_eq!(problems[3].to_string(), "201 is listed by DC and VA.");
+        assert!(ZipPrefixTable::new(&file).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::OnceLock;
+
+use crate::zip_prefixes::{parse_range, ZipPrefixTable};
+use crate::{Province, UnexpectedError};
+
+// SHIPPING ZONES
+//
+// Carriers price by zone, 1 (local) to 8 (farthest in the lower 48), with 9
+// for the Pacific territories. A zone chart for our origin (the USPS one at
+// https://postcalc.usps.com/DomesticZoneChart, saved in the shape below)
+// gives the exact zone of every destination prefix. Without one the zone is
+// estimated the way USPS builds its charts: from the distance between the
+// origin and destination prefixes, using the USPS distance bands. Each
+// prefix is placed at the city of its sectional center facility, from
+// src/prefix_centers.json, so the estimate can be a zone off near a band's
+// edge.
+
+pub const DEFAULT_ZONE_CHART_PATH: &str = "zone_chart.json";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct ZoneConfig {
+    /// The zip orders ship from. Without it orders have no zone.
+    pub origin_zip: String,
+}
+
+/// The zones from one origin prefix to destination prefixes.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ZoneChart {
+    /// The 3 digit prefix the chart is for, e.g. "100".
+    pub origin: String,
+    /// Zone to destination prefix ranges like "005-089" or "100".
+    pub zones: BTreeMap<u8, Vec<String>>,
+}
+
+impl ZoneChart {
+    /// Loads the chart at `path`, if there is one.
+    pub fn load(path: &str) -> Result<Option<ZoneChart>, Box<dyn Error>> {
+        crate::read_optional(path, DEFAULT_ZONE_CHART_PATH)?
+            .map(|contents| Ok(serde_json::from_str(&contents)?))
+            .transpose()
+    }
+}
+
+/// Great circle distance in miles between two (latitude, longitude) points.
+fn distance_miles(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * 3958.8 * a.sqrt().asin()
+}
+
+/// The USPS zone of a distance in miles.
+fn zone_for_distance(miles: f64) -> u8 {
+    match miles {
+        d if d <= 50.0 => 1,
+        d if d <= 150.0 => 2,
+        d if d <= 300.0 => 3,
+        d if d <= 600.0 => 4,
+        d if d <= 1000.0 => 5,
+        d if d <= 1400.0 => 6,
+        d if d <= 1800.0 => 7,
+        _ => 8,
+    }
+}
+
+/// The (latitude, longitude) of each 3-digit prefix's sectional center
+/// facility, indexed by prefix. Military prefixes have none.
+fn prefix_centers() -> &'static Vec<Option<(f64, f64)>> {
+    static CENTERS: OnceLock<Vec<Option<(f64, f64)>>> = OnceLock::new();
+    CENTERS.get_or_init(|| {
+        let ranges: BTreeMap<String, (f64, f64)> =
+            serde_json::from_str(include_str!("prefix_centers.json"))
+                .expect("built-in prefix centers are valid");
+        let mut centers = vec![None; 1000];
+        for (range, center) in ranges {
+            let prefixes = parse_range(&range, 3).expect("built-in prefix ranges are valid");
+            for prefix in prefixes {
+                centers[prefix as usize] = Some(center);
+            }
+        }
+        centers
+    })
+}
+
+fn prefix_center(zip: u32) -> Option<(f64, f64)> {
+    prefix_centers()
+        .get((zip / 100) as usize)
+        .copied()
+        .flatten()
+}
+
+/// Where an order's zone came from, so exact zones can be told apart from
+/// estimates in the output.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ZoneSource {
+    /// Looked up in the zone chart.
+    Chart,
+    /// Estimated from the distance between prefixes.
+    Estimate,
+}
+
+impl ZoneSource {
+    pub fn name(&self) -> String {
+        match self {
+            ZoneSource::Chart => String::from("chart"),
+            ZoneSource::Estimate => String::from("estimate"),
+        }
+    }
+}
+
+pub enum ZoneCalculator {
+    /// No origin, so no zones.
+    NoOrigin,
+    /// Zone by destination prefix, from a chart.
+    Chart(Vec<Option<u8>>),
+    /// Zone estimated from the distance between prefixes.
+    Distance {
+        origin: Province,
+        center: (f64, f64),
+    },
+}
+
+impl ZoneCalculator {
+    /// A calculator for `origin_zip`, or for the chart's origin if no zip is
+    /// given. The chart has to be for the origin's prefix. Without a chart the
+    /// origin has to be a civilian US zip; its state is looked up in `zips`.
+    pub fn new(
+        origin_zip: &str,
+        chart: Option<&ZoneChart>,
+        zips: &ZipPrefixTable,
+    ) -> Result<ZoneCalculator, UnexpectedError> {
+        let origin_zip = origin_zip.trim();
+        let Some(chart) = chart else {
+            if origin_zip.is_empty() {
+                return Ok(ZoneCalculator::NoOrigin);
+            }
+            let zip = crate::zip_as_u32(origin_zip).ok();
+            let origin = zip.and_then(|zip| Province::from_zip_code(zip, zips).ok());
+            let center = zip.and_then(prefix_center);
+            let (Some(origin), Some(center)) = (origin, center) else {
+                return Err(UnexpectedError {
+                    message: format!("Origin zip \"{}\" isn't a US zip.", origin_zip),
+                });
+            };
+            return Ok(ZoneCalculator::Distance { origin, center });
+        };
+
+        if !origin_zip.is_empty() && !origin_zip.starts_with(&chart.origin) {
+            return Err(UnexpectedError {
+                message: format!(
+                    "The zone chart is for origin {}, not {}.",
+                    chart.origin, origin_zip
+                ),
+            });
+        }
+        let mut zones = vec![None; 1000];
+        for (zone, ranges) in &chart.zones {
+            if !(1..=9).contains(zone) {
+                return Err(UnexpectedError {
+                    message: format!("Zone {} is not between 1 and 9.", zone),
+                });
+            }
+            for range in ranges {
+                let prefixes = parse_range(range, 3).ok_or_else(|| UnexpectedError {
+                    message: format!("Invalid zip prefix range \"{}\".", range),
+                })?;
+                for prefix in prefixes {
+                    if let Some(other) = zones[prefix as usize].replace(*zone) {
+                        return Err(UnexpectedError {
+                            message: format!(
+                                "{:03} is in zone {} and zone {}.",
+                                prefix, other, zone
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(ZoneCalculator::Chart(zones))
+    }
+
+    /// Where the zones this calculator gives come from.
+    pub fn source(&self) -> Option<ZoneSource> {
+        match self {
+            ZoneCalculator::NoOrigin => None,
+            ZoneCalculator::Chart(_) => Some(ZoneSource::Chart),
+            ZoneCalculator::Distance { .. } => Some(ZoneSource::Estimate),
+        }
+    }
+
+    /// The zone to a destination in `state` at `zip`. Addresses abroad,
+    /// military mail and prefixes missing from the chart have none.
+    pub fn zone_for(&self, state: Option<Province>, zip: &str) -> Option<u8> {
+        let state = state?;
+        let zip = crate::zip_as_u32(zip).ok()?;
+        match self {
+            ZoneCalculator::NoOrigin => None,
+            ZoneCalculator::Chart(zones) => zones.get((zip / 100) as usize).copied().flatten(),
+            ZoneCalculator::Distance { origin, center } => {
+                use Province::*;
+                // The Pacific territories are zone 9 whatever the distance.
+                if [*origin, state]
+                    .iter()
+                    .any(|state| matches!(state, GU | AS | MP))
+                {
+                    return Some(9);
+                }
+                Some(zone_for_distance(distance_miles(
+                    *center,
+                    prefix_center(zip)?,
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zones_from_distance() {
+        let zips = ZipPrefixTable::built_in();
+        let calculator = ZoneCalculator::new("10001", None, &zips).unwrap();
+        let zone = |zip: &str| {
+            let state = Province::from_zip_code(crate::zip_as_u32(zip).unwrap(), &zips).ok();
+            calculator.zone_for(state, zip)
+        };
+        assert_eq!(zone("10016"), Some(1));
+        assert_eq!(zone("07302"), Some(1));
+        assert_eq!(zone("19103"), Some(2));
+        assert_eq!(zone("21201"), Some(3));
+        assert_eq!(zone("13202"), Some(3));
+        assert_eq!(zone("44114"), Some(4));
+        assert_eq!(zone("60601"), Some(5));
+        assert_eq!(zone("80202"), Some(7));
+        assert_eq!(zone("94105"), Some(8));
+        assert_eq!(zone("96910"), Some(9));
+        assert_eq!(zone("09001"), None);
+        assert_eq!(calculator.zone_for(None, "10001"), None);
+        assert!(ZoneCalculator::new("21300", None, &zips).is_err());
+        assert!(ZoneCalculator::new("09001", None, &zips).is_err());
+        assert_eq!(calculator.source(), Some(ZoneSource::Estimate));
+    }
+
+    #[test]
+    fn test_every_civilian_prefix_has_a_center() {
+        use Province::*;
+        let zips = ZipPrefixTable::built_in();
+        for prefix in 0..1000 {
+            let Ok(state) = Province::from_zip_code(prefix * 100, &zips) else {
+                continue;
+            };
+            if !matches!(state, AA | AE | AP) {
+                assert!(prefix_center(prefix * 100).is_some(), "{:03}", prefix);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zones_from_chart() {
+        let zips = ZipPrefixTable::built_in();
+        let chart: ZoneChart = serde_json::from_str(
+            r#"{ "origin": "100", "zones": { "1": ["100-102"], "2": ["070-089"], "8": ["900-961"] } }"#,
+        )
+        .unwrap();
+        let calculator = ZoneCalculator::new("", Some(&chart), &zips).unwrap();
+        assert_eq!(calculator.zone_for(Some(Province::NY), "10016"), Some(1));
+        assert_eq!(calculator.zone_for(Some(Province::NJ), "07302"), Some(2));
+        assert_eq!(calculator.zone_for(Some(Province::CA), "94105"), Some(8));
+        assert_eq!(calculator.zone_for(Some(Province::TX), "75201"), None);
+        assert_eq!(calculator.source(), Some(ZoneSource::Chart));
+        assert!(ZoneCalculator::new("94105", Some(&chart), &zips).is_err());
+
+        let overlapping: ZoneChart = serde_json::from_str(
+            r#"{ "origin": "100", "zones": { "1": ["100-102"], "2": ["102"] } }"#,
+        )
+        .unwrap();
+        assert!(ZoneCalculator::new("", Some(&overlapping), &zips).is_err());
+    }
+
+    #[test]
+    fn test_no_origin_no_zone() {
+        let zips = ZipPrefixTable::built_in();
+        let calculator = ZoneCalculator::new(" ", None, &zips).unwrap();
+        assert_eq!(calculator.zone_for(Some(Province::NY), "10016"), None);
+        assert_eq!(calculator.source(), None);
+    }
+}